@@ -1,89 +1,227 @@
-use std::str::Lines;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    str::Lines,
+};
 
+use crate::params::Params;
 use crate::Solver;
 
 pub struct Solver1;
 
+const NUMERALS: [(&str, u32); 9] = [
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const ENGLISH: [(&str, u32); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
 impl Solver for Solver1 {
     fn day_number(&self) -> u32 {
         1
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let mut total = 0;
+        self.part1_with_params(input_lines, &Params::new())
+    }
 
-        for line in input_lines {
-            let digits: Vec<u32> = line
-                .matches(char::is_numeric)
-                .map(|s| s.parse::<u32>().unwrap())
-                .collect();
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
+    }
 
-            if digits.len() == 0 {
-                panic!("{}", line);
-            }
-            total += digits[0] * 10 + digits[digits.len() - 1];
-        }
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let matcher = WordMatcher::new(&NUMERALS);
+        calibration_sum(input_lines, &matcher, MatchMode::from_params(params)).to_string()
+    }
+
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let matcher = WordMatcher::new(&[NUMERALS, ENGLISH].concat());
+        calibration_sum(input_lines, &matcher, MatchMode::from_params(params)).to_string()
+    }
+}
 
-        total.to_string()
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum MatchMode {
+    // Every match in a single pass, including overlapping ones
+    AllMatches,
+    // First match from the start and last from the end, without scanning the middle
+    FromEnds,
+}
+
+impl MatchMode {
+    fn from_params(params: &Params) -> MatchMode {
+        match params.get("match").unwrap_or("ends") {
+            "all" => MatchMode::AllMatches,
+            "ends" => MatchMode::FromEnds,
+            other => panic!("Unknown match mode {other}"),
+        }
     }
+}
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut total = 0;
-
-        for line in input_lines {
-            let mut digits = Vec::<u32>::new();
-            let chars = line.char_indices();
-            for my_char in chars {
-                if my_char.1.is_numeric() {
-                    digits.push(my_char.1.to_digit(10).unwrap());
-                } else if let Some(digit) = find_digit_word(&line[my_char.0..]) {
-                    digits.push(digit);
-                }
+fn calibration_sum(input_lines: Lines, matcher: &WordMatcher, mode: MatchMode) -> u32 {
+    input_lines
+        .map(|line| {
+            let (first, last) = matcher
+                .first_and_last(line, mode)
+                .unwrap_or_else(|| panic!("No digits in {line}"));
+            first * 10 + last
+        })
+        .sum()
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    value: Option<u32>,
+    depth: usize,
+    // Longest proper suffix of this node's text which is also in the trie
+    failure: usize,
+    next_word_end: Option<usize>,
+}
+
+// Aho-Corasick automaton, working on chars so that words and lines can be any Unicode
+struct WordMatcher {
+    nodes: Vec<TrieNode>,
+}
+
+impl WordMatcher {
+    fn new(words: &[(&str, u32)]) -> WordMatcher {
+        let mut nodes = vec![TrieNode::default()];
+
+        for (word, value) in words {
+            let mut node = 0;
+            for c in word.chars() {
+                node = match nodes[node].children.get(&c) {
+                    Some(child) => *child,
+                    None => {
+                        let child = nodes.len();
+                        let depth = nodes[node].depth + 1;
+                        nodes.push(TrieNode {
+                            depth,
+                            ..Default::default()
+                        });
+                        nodes[node].children.insert(c, child);
+                        child
+                    }
+                };
             }
+            nodes[node].value = Some(*value);
+        }
+
+        // Breadth first, so that the failure node of each parent is set before its children
+        let mut queue: VecDeque<usize> = nodes[0].children.values().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            let children: Vec<(char, usize)> =
+                nodes[node].children.iter().map(|(c, n)| (*c, *n)).collect();
+
+            for (c, child) in children {
+                let mut failure = nodes[node].failure;
+                let child_failure = loop {
+                    if let Some(next) = nodes[failure].children.get(&c) {
+                        break *next;
+                    }
+                    if failure == 0 {
+                        break 0;
+                    }
+                    failure = nodes[failure].failure;
+                };
 
-            if digits.len() == 0 {
-                panic!("{}", line);
+                nodes[child].failure = child_failure;
+                nodes[child].next_word_end = if nodes[child_failure].value.is_some() {
+                    Some(child_failure)
+                } else {
+                    nodes[child_failure].next_word_end
+                };
+                queue.push_back(child);
             }
-            total += digits[0] * 10 + digits[digits.len() - 1];
         }
 
-        total.to_string()
+        WordMatcher { nodes }
     }
-}
 
-fn find_digit_word(word: &str) -> Option<u32> {
-    let len = word.len();
+    // (start position in chars, value), ordered by where they end
+    fn find_all(&self, text: &str) -> Vec<(usize, u32)> {
+        let mut matches = Vec::new();
+        let mut node = 0;
 
-    if len < 3 {
-        return None;
-    }
-    match &word[0..3] {
-        "one" => return Some(1),
-        "two" => return Some(2),
-        "six" => return Some(6),
-        _ => (),
-    }
+        for (ix, c) in text.chars().enumerate() {
+            node = loop {
+                if let Some(next) = self.nodes[node].children.get(&c) {
+                    break *next;
+                }
+                if node == 0 {
+                    break 0;
+                }
+                node = self.nodes[node].failure;
+            };
 
-    if len < 4 {
-        return None;
+            let mut word_end = if self.nodes[node].value.is_some() {
+                Some(node)
+            } else {
+                self.nodes[node].next_word_end
+            };
+            while let Some(end) = word_end {
+                let end_node = &self.nodes[end];
+                matches.push((ix + 1 - end_node.depth, end_node.value.unwrap()));
+                word_end = end_node.next_word_end;
+            }
+        }
+
+        matches
     }
-    match &word[0..4] {
-        "zero" => return Some(0),
-        "four" => return Some(4),
-        "five" => return Some(5),
-        "nine" => return Some(9),
-        _ => (),
+
+    fn match_at_start(&self, text: &str) -> Option<u32> {
+        let mut node = 0;
+        for c in text.chars() {
+            node = *self.nodes[node].children.get(&c)?;
+            if let Some(value) = self.nodes[node].value {
+                return Some(value);
+            }
+        }
+        None
     }
 
-    if len < 5 {
-        return None;
+    // Where two words start at the same place, the shorter one is used
+    fn first_and_last(&self, text: &str, mode: MatchMode) -> Option<(u32, u32)> {
+        match mode {
+            MatchMode::AllMatches => {
+                let matches = self.find_all(text);
+
+                // min_by_key keeps the first of several equal elements, and where words start at
+                // the same place the shorter one ends (so appears) first
+                let first = matches.iter().min_by_key(|(start, _)| *start)?;
+                let last = matches.iter().min_by_key(|(start, _)| Reverse(*start))?;
+                Some((first.1, last.1))
+            }
+            MatchMode::FromEnds => {
+                let starts = text.char_indices().map(|(ix, _)| ix);
+                let first = starts
+                    .clone()
+                    .find_map(|ix| self.match_at_start(&text[ix..]))?;
+                let last = starts
+                    .rev()
+                    .find_map(|ix| self.match_at_start(&text[ix..]))?;
+                Some((first, last))
+            }
+        }
     }
-    return match &word[0..5] {
-        "three" => Some(3),
-        "seven" => Some(7),
-        "eight" => Some(8),
-        _ => None,
-    };
 }
 
 #[cfg(test)]
@@ -108,4 +246,94 @@ zoneight234
 7pqrstsixteen";
         assert_eq!(super::Solver1.part2(sample_input.lines()), "281");
     }
+
+    #[test]
+    fn match_modes_agree() {
+        use super::{calibration_sum, MatchMode, WordMatcher, ENGLISH, NUMERALS};
+
+        let sample_input = "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen
+oneight
+9";
+        let matcher = WordMatcher::new(&[NUMERALS, ENGLISH].concat());
+        for mode in [MatchMode::AllMatches, MatchMode::FromEnds] {
+            assert_eq!(
+                calibration_sum(sample_input.lines(), &matcher, mode),
+                281 + 18 + 99
+            );
+        }
+    }
+
+    #[test]
+    fn match_param() {
+        use crate::params::Params;
+
+        let sample_input = "two1nine
+eightwothree
+zoneight234
+oneight";
+        for mode in ["all", "ends"] {
+            let mut params = Params::new();
+            params.insert(&format!("match={mode}"));
+            assert_eq!(
+                super::Solver1.part2_with_params(sample_input.lines(), &params),
+                (29 + 83 + 14 + 18).to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_words() {
+        use super::{WordMatcher, ENGLISH};
+
+        let matcher = WordMatcher::new(&ENGLISH);
+        assert_eq!(
+            matcher.find_all("xtwoneightwo"),
+            vec![(1, 2), (3, 1), (5, 8), (9, 2)]
+        );
+    }
+
+    #[test]
+    fn other_languages() {
+        use super::{MatchMode, WordMatcher, NUMERALS};
+
+        let german = [
+            ("eins", 1),
+            ("zwei", 2),
+            ("drei", 3),
+            ("vier", 4),
+            ("fünf", 5),
+            ("sechs", 6),
+            ("sieben", 7),
+            ("acht", 8),
+            ("neun", 9),
+        ];
+        let chinese = [
+            ("一", 1),
+            ("二", 2),
+            ("三", 3),
+            ("四", 4),
+            ("五", 5),
+            ("六", 6),
+            ("七", 7),
+            ("八", 8),
+            ("九", 9),
+        ];
+
+        for mode in [MatchMode::AllMatches, MatchMode::FromEnds] {
+            let matcher = WordMatcher::new(&[NUMERALS, german].concat());
+            assert_eq!(matcher.first_and_last("ßfünfx7zweiß", mode), Some((5, 2)));
+            assert_eq!(matcher.first_and_last("achteins", mode), Some((8, 1)));
+            assert_eq!(matcher.first_and_last("einsame", mode), Some((1, 1)));
+            assert_eq!(matcher.first_and_last("éé", mode), None);
+
+            let matcher = WordMatcher::new(&chinese);
+            assert_eq!(matcher.first_and_last("价格是三百九十", mode), Some((3, 9)));
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use crate::geometry::{Point, Polygon};
+use crate::params::Params;
 use crate::Solver;
 use std::{
     cell::RefCell,
@@ -222,159 +224,210 @@ impl Solver for Solver10 {
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        let mut map: Vec<Vec<Pipe>> = Vec::new();
+        self.part2_with_params(input_lines, &Params::new())
+    }
 
-        let mut start: (usize, usize) = (0, 0);
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let method = match params.get("enclosed").unwrap_or("picks") {
+            "turns" => EnclosedTileMethod::TurnScan,
+            "picks" => EnclosedTileMethod::PicksTheorem,
+            other => panic!("Unknown enclosed tile method {other}"),
+        };
+        count_enclosed_tiles(input_lines, method)
+    }
+}
 
-        for (y, line) in input_lines.enumerate() {
-            let mut row: Vec<Pipe> = Vec::new();
-            for (x, c) in line.chars().enumerate() {
-                let new_pipe = Pipe::new(c, x as i32, y as i32);
-                if new_pipe.pipetype == PipeType::Start {
-                    start = (new_pipe.x as usize, new_pipe.y as usize);
-                }
-                row.push(new_pipe);
-            }
-            map.push(row);
-        }
+// Ways of counting the tiles enclosed by the main loop, which should all agree
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum EnclosedTileMethod {
+    // Scan in from the loop on the side with more turns
+    TurnScan,
+    // Interior lattice points of the loop as a polygon
+    PicksTheorem,
+}
 
-        let start: &Pipe = get_location(&map, start.0, start.1).unwrap();
+fn count_enclosed_tiles(input_lines: Lines, method: EnclosedTileMethod) -> String {
+    let mut map: Vec<Vec<Pipe>> = Vec::new();
 
-        // Find which direction to go from the start
-        let mut s_connections: Vec<Direction> = Vec::new();
-        let mut next_loc_dir: (i32, i32, Direction) = (0, 0, Direction::N);
-
-        if start.x > 0 {
-            // x - 1, y
-            if let Some(next) = get_location(
-                &map,
-                (start.x - 1).try_into().unwrap(),
-                start.y.try_into().unwrap(),
-            ) {
-                s_connections.push(Direction::L);
-                next_loc_dir = (next.x, next.y, Direction::L);
+    let mut start: (usize, usize) = (0, 0);
+
+    for (y, line) in input_lines.enumerate() {
+        let mut row: Vec<Pipe> = Vec::new();
+        for (x, c) in line.chars().enumerate() {
+            let new_pipe = Pipe::new(c, x as i32, y as i32);
+            if new_pipe.pipetype == PipeType::Start {
+                start = (new_pipe.x as usize, new_pipe.y as usize);
             }
+            row.push(new_pipe);
         }
-        // x + 1, y
+        map.push(row);
+    }
+
+    let start: &Pipe = get_location(&map, start.0, start.1).unwrap();
+
+    // Find which direction to go from the start
+    let mut s_connections: Vec<Direction> = Vec::new();
+    let mut next_loc_dir: (i32, i32, Direction) = (0, 0, Direction::N);
+
+    if start.x > 0 {
+        // x - 1, y
         if let Some(next) = get_location(
             &map,
-            (start.x + 1).try_into().unwrap(),
+            (start.x - 1).try_into().unwrap(),
             start.y.try_into().unwrap(),
         ) {
-            s_connections.push(Direction::R);
-            next_loc_dir = (next.x, next.y, Direction::R);
-        }
-        if start.y > 0 {
-            // x, y - 1
-            if let Some(next) = get_location(
-                &map,
-                start.x.try_into().unwrap(),
-                (start.y - 1).try_into().unwrap(),
-            ) {
-                s_connections.push(Direction::U);
-                next_loc_dir = (next.x, next.y, Direction::U);
-            }
+            s_connections.push(Direction::L);
+            next_loc_dir = (next.x, next.y, Direction::L);
         }
-        // x, y + 1
+    }
+    // x + 1, y
+    if let Some(next) = get_location(
+        &map,
+        (start.x + 1).try_into().unwrap(),
+        start.y.try_into().unwrap(),
+    ) {
+        s_connections.push(Direction::R);
+        next_loc_dir = (next.x, next.y, Direction::R);
+    }
+    if start.y > 0 {
+        // x, y - 1
         if let Some(next) = get_location(
             &map,
             start.x.try_into().unwrap(),
-            (start.y + 1).try_into().unwrap(),
+            (start.y - 1).try_into().unwrap(),
         ) {
-            s_connections.push(Direction::D);
-            next_loc_dir = (next.x, next.y, Direction::D);
+            s_connections.push(Direction::U);
+            next_loc_dir = (next.x, next.y, Direction::U);
         }
+    }
+    // x, y + 1
+    if let Some(next) = get_location(
+        &map,
+        start.x.try_into().unwrap(),
+        (start.y + 1).try_into().unwrap(),
+    ) {
+        s_connections.push(Direction::D);
+        next_loc_dir = (next.x, next.y, Direction::D);
+    }
 
-        let start_x: usize = start.x.try_into().unwrap();
-        let start_y: usize = start.y.try_into().unwrap();
-
-        start
-            .directions
-            .borrow_mut()
-            .extend(Pipe::create_pipe_hashmap(
-                s_connections[0],
-                s_connections[1],
-            ));
-
-        let mut current: (&Pipe, Direction) = (
-            get_location(
-                &map,
-                next_loc_dir.0.try_into().unwrap(),
-                next_loc_dir.1.try_into().unwrap(),
-            )
-            .unwrap(),
-            next_loc_dir.2,
-        );
+    let start_x: usize = start.x.try_into().unwrap();
+    let start_y: usize = start.y.try_into().unwrap();
 
-        let mut previous: &Pipe = get_location(&map, start_x, start_y).unwrap();
+    start
+        .directions
+        .borrow_mut()
+        .extend(Pipe::create_pipe_hashmap(
+            s_connections[0],
+            s_connections[1],
+        ));
 
-        // map[start_y][start_x] = replacement_start;
+    let mut current: (&Pipe, Direction) = (
+        get_location(
+            &map,
+            next_loc_dir.0.try_into().unwrap(),
+            next_loc_dir.1.try_into().unwrap(),
+        )
+        .unwrap(),
+        next_loc_dir.2,
+    );
 
-        let mut turn_counts = vec![0, 0];
-        let mut loop_elems: Vec<(&Pipe, Direction)> = Vec::new();
-        let mut locations_of_main_loop: HashSet<(i32, i32)> = HashSet::new();
+    let mut previous: &Pipe = get_location(&map, start_x, start_y).unwrap();
 
-        // Dont add the start element, as it'll be put into the sets when we find it at the end of the loop
-        loop_elems.push(current);
-        locations_of_main_loop.insert((current.0.x, current.0.y));
+    // map[start_y][start_x] = replacement_start;
 
-        while current.0.pipetype != PipeType::Start {
-            let (next_location, turn, dir) = current.0.get_next_location(previous);
+    let mut turn_counts = [0, 0];
+    let mut loop_elems: Vec<(&Pipe, Direction)> = Vec::new();
+    let mut locations_of_main_loop: HashSet<(i32, i32)> = HashSet::new();
 
-            match turn {
-                Turn::L => turn_counts[0] += 1,
-                Turn::R => turn_counts[1] += 1,
-                _ => (),
-            }
-            previous = current.0;
-            current = (
-                get_location(&map, next_location.0 as usize, next_location.1 as usize).unwrap(),
-                dir,
-            );
-            locations_of_main_loop.insert((current.0.x, current.0.y));
-            loop_elems.push(current);
+    // Dont add the start element, as it'll be put into the sets when we find it at the end of the loop
+    loop_elems.push(current);
+    locations_of_main_loop.insert((current.0.x, current.0.y));
+
+    while current.0.pipetype != PipeType::Start {
+        let (next_location, turn, dir) = current.0.get_next_location(previous);
+
+        match turn {
+            Turn::L => turn_counts[0] += 1,
+            Turn::R => turn_counts[1] += 1,
+            _ => (),
+        }
+        previous = current.0;
+        current = (
+            get_location(&map, next_location.0 as usize, next_location.1 as usize).unwrap(),
+            dir,
+        );
+        locations_of_main_loop.insert((current.0.x, current.0.y));
+        loop_elems.push(current);
+    }
+
+    let tiles_inside = match method {
+        EnclosedTileMethod::TurnScan => {
+            count_by_turn_scan(&map, loop_elems, turn_counts, &locations_of_main_loop)
         }
+        EnclosedTileMethod::PicksTheorem => count_by_picks_theorem(&loop_elems),
+    };
 
-        let more_lefts = turn_counts[0] > turn_counts[1];
+    tiles_inside.to_string()
+}
 
-        let search_dir = if more_lefts {
-            Direction::D
-        } else {
-            Direction::U
+fn count_by_turn_scan(
+    map: &[Vec<Pipe>],
+    loop_elems: Vec<(&Pipe, Direction)>,
+    turn_counts: [u32; 2],
+    locations_of_main_loop: &HashSet<(i32, i32)>,
+) -> u32 {
+    let more_lefts = turn_counts[0] > turn_counts[1];
+
+    let search_dir = if more_lefts {
+        Direction::D
+    } else {
+        Direction::U
+    };
+
+    let mut tiles_inside = 0;
+
+    // to count included tiles:
+    // - only count in one direction from the track - we'll choose ones to the right
+    // - only count when going up (if there are more right turns) or down (if there are more left turns)
+    // - count until you hit a tile that's part of the loop
+    for (p, dir) in loop_elems {
+        let input_side = match dir {
+            Direction::U => Direction::D,
+            Direction::D => Direction::U,
+            Direction::L => Direction::R,
+            Direction::R => Direction::L,
+            _ => panic!("no input side"),
         };
+        let next_dir = *p.directions.borrow().get(&input_side).unwrap();
 
-        let mut tiles_inside = 0;
-
-        // to count included tiles:
-        // - only count in one direction from the track - we'll choose ones to the right
-        // - only count when going up (if there are more right turns) or down (if there are more left turns)
-        // - count until you hit a tile that's part of the loop
-        for (p, dir) in loop_elems {
-            let input_side = match dir {
-                Direction::U => Direction::D,
-                Direction::D => Direction::U,
-                Direction::L => Direction::R,
-                Direction::R => Direction::L,
-                _ => panic!("no input side"),
-            };
-            let next_dir = *p.directions.borrow().get(&input_side).unwrap();
-
-            if dir == search_dir || next_dir == search_dir {
-                let row = map.get(p.y as usize).unwrap();
-                for location in row.iter().skip(p.x as usize + 1) {
-                    if locations_of_main_loop.contains(&(location.x, location.y)) {
-                        break;
-                    }
-                    tiles_inside += 1;
+        if dir == search_dir || next_dir == search_dir {
+            let row = map.get(p.y as usize).unwrap();
+            for location in row.iter().skip(p.x as usize + 1) {
+                if locations_of_main_loop.contains(&(location.x, location.y)) {
+                    break;
                 }
+                tiles_inside += 1;
             }
         }
-
-        tiles_inside.to_string()
     }
+
+    tiles_inside
 }
 
-fn get_location<'a>(map: &'a Vec<Vec<Pipe>>, x_ix: usize, y_ix: usize) -> Option<&'a Pipe> {
+fn count_by_picks_theorem(loop_elems: &[(&Pipe, Direction)]) -> u32 {
+    // Every tile of the loop is a vertex, so the boundary points are exactly the loop tiles.
+    let vertices = loop_elems
+        .iter()
+        .map(|(p, _)| Point {
+            x: p.x as i64,
+            y: p.y as i64,
+        })
+        .collect();
+
+    Polygon::new(vertices).interior_points() as u32
+}
+
+fn get_location(map: &[Vec<Pipe>], x_ix: usize, y_ix: usize) -> Option<&Pipe> {
     if let Some(row) = map.get(y_ix) {
         if let Some(pipe) = row.get(x_ix) {
             return Some(pipe);
@@ -439,4 +492,77 @@ L.L7LFJ|||||FJL7||LJ
 L7JLJL-JLJLJL--JLJ.L";
         assert_eq!(super::Solver10.part2(sample_input.lines()), "10");
     }
+
+    #[test]
+    fn enclosed_tile_methods_agree() {
+        use super::{count_enclosed_tiles, EnclosedTileMethod};
+
+        let samples = [
+            "...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........",
+            ".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...",
+            "FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L",
+        ];
+
+        for (sample_input, expected) in samples.iter().zip(["4", "8", "10"]) {
+            for method in [
+                EnclosedTileMethod::TurnScan,
+                EnclosedTileMethod::PicksTheorem,
+            ] {
+                assert_eq!(
+                    count_enclosed_tiles(sample_input.lines(), method),
+                    expected,
+                    "{method:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn enclosed_param() {
+        use crate::params::Params;
+
+        let sample_input = "..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........";
+        for method in ["turns", "picks"] {
+            let mut params = Params::new();
+            params.insert(&format!("enclosed={method}"));
+            assert_eq!(
+                super::Solver10.part2_with_params(sample_input.lines(), &params),
+                "4"
+            );
+        }
+    }
 }
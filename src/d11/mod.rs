@@ -1,5 +1,6 @@
+use crate::params::Params;
 use crate::Solver;
-use std::{str::Lines, vec};
+use std::str::Lines;
 
 pub struct Solver11;
 
@@ -9,11 +10,19 @@ impl Solver for Solver11 {
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        solve_with_expansion_factor(input_lines, 2)
+        self.part1_with_params(input_lines, &Params::new())
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        solve_with_expansion_factor(input_lines, 1000000)
+        self.part2_with_params(input_lines, &Params::new())
+    }
+
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve_with_expansion_factor(input_lines, params.get_or("expansion", 2))
+    }
+
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve_with_expansion_factor(input_lines, params.get_or("expansion", 1000000))
     }
 }
 
@@ -22,72 +31,54 @@ fn solve_with_expansion_factor(input_lines: Lines, factor: i64) -> String {
     let num_columns = lines_vec[0].chars().count();
     let num_rows = lines_vec.len();
 
-    let mut galaxies_on_single_row: Vec<char> = vec!['.'; num_columns];
-    let mut galaxies_on_single_column: Vec<char> = vec!['.'; num_rows];
+    let mut xs: Vec<usize> = Vec::new();
+    let mut ys: Vec<usize> = Vec::new();
 
-    let mut galaxies: Vec<(i64, i64)> = Vec::new();
-
-    // 1:  for each found galaxy:
-    // - add to the list of galaxies
-    // - mark the row and column as having a galaxy
     for (y, row) in lines_vec.iter().enumerate() {
         for (x, c) in row.char_indices() {
             if c == '#' {
-                galaxies.push((x.try_into().unwrap(), y.try_into().unwrap()));
-                galaxies_on_single_row[x] = '#';
-                galaxies_on_single_column[y] = '#';
+                xs.push(x);
+                ys.push(y);
             }
         }
     }
 
-    // 2: get a lists of row/column indices which have no galaxies
-    let empty_column_ixs = galaxies_on_single_row
-        .iter()
-        .enumerate()
-        .filter(|(_, c)| c == &&'.')
-        .map(|(ix, _)| ix.try_into().unwrap())
-        .collect::<Vec<i64>>();
-    let empty_row_ixs = galaxies_on_single_column
-        .iter()
-        .enumerate()
-        .filter(|(_, c)| c == &&'.')
-        .map(|(ix, _)| ix.try_into().unwrap())
-        .collect::<Vec<i64>>();
-
-    let mut dist_sum = 0;
-
-    // 3: for each pair of galaxies, calculate the distance between them
-    for left_ix in 0..galaxies.len() {
-        let l_gal = galaxies[left_ix];
-        for r_gal in galaxies[left_ix + 1..].iter() {
-            // The offset in each direction is the number of empty rows/columns indices which we've passed
-            let left_offset_x: i64 = (factor - 1)
-                * TryInto::<i64>::try_into(
-                    empty_column_ixs.iter().filter(|ix| ix < &&l_gal.0).count(),
-                )
-                .unwrap();
-            let right_offset_x: i64 = (factor - 1)
-                * TryInto::<i64>::try_into(
-                    empty_column_ixs.iter().filter(|ix| ix < &&r_gal.0).count(),
-                )
-                .unwrap();
-            let left_offset_y: i64 = (factor - 1)
-                * TryInto::<i64>::try_into(
-                    empty_row_ixs.iter().filter(|ix| ix < &&l_gal.1).count(),
-                )
-                .unwrap();
-            let right_offset_y: i64 = (factor - 1)
-                * TryInto::<i64>::try_into(
-                    empty_row_ixs.iter().filter(|ix| ix < &&r_gal.1).count(),
-                )
-                .unwrap();
-            let x_dist = ((l_gal.0 + left_offset_x) - (r_gal.0 + right_offset_x)).abs();
-            let y_dist = ((l_gal.1 + left_offset_y) - (r_gal.1 + right_offset_y)).abs();
-            dist_sum += x_dist + y_dist;
-        }
+    // The rows and columns are independent, so the distances can be summed separately for each
+    let x_dist = sum_of_distances(expand(&xs, num_columns, factor));
+    let y_dist = sum_of_distances(expand(&ys, num_rows, factor));
+
+    (x_dist + y_dist).to_string()
+}
+
+// Each empty row (or column) is replaced by `factor` of them
+fn expand(coords: &[usize], size: usize, factor: i64) -> Vec<i64> {
+    let mut occupied = vec![false; size];
+    for c in coords {
+        occupied[*c] = true;
     }
 
-    dist_sum.to_string()
+    // expanded[c] is where c moves to
+    let mut expanded = Vec::with_capacity(size);
+    let mut next = 0;
+    for is_occupied in occupied {
+        expanded.push(next);
+        next += if is_occupied { 1 } else { factor };
+    }
+
+    coords.iter().map(|c| expanded[*c]).collect()
+}
+
+// Once they're sorted, the ith position is added for each of the i before it and subtracted for
+// each of those after it
+fn sum_of_distances(mut positions: Vec<i64>) -> i64 {
+    positions.sort_unstable();
+    let n = positions.len() as i64;
+
+    positions
+        .iter()
+        .enumerate()
+        .map(|(ix, position)| position * (2 * ix as i64 - n + 1))
+        .sum()
 }
 
 #[cfg(test)]
@@ -129,4 +120,53 @@ mod tests {
             "8410"
         );
     }
+
+    const SAMPLE_INPUT: &str = "...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
+    #[test]
+    fn expansion_param() {
+        use crate::params::Params;
+
+        let mut params = Params::new();
+        params.insert("expansion=10");
+        assert_eq!(
+            super::Solver11.part1_with_params(SAMPLE_INPUT.lines(), &params),
+            "1030"
+        );
+        assert_eq!(
+            super::Solver11.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "1030"
+        );
+
+        // No expansion at all
+        params.insert("expansion=1");
+        assert_eq!(
+            super::Solver11.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "292"
+        );
+    }
+
+    #[test]
+    fn sum_of_distances() {
+        use super::sum_of_distances;
+
+        let positions: Vec<i64> = vec![5, -3, 8, 0, 5, 12];
+        let mut expected = 0;
+        for (ix, a) in positions.iter().enumerate() {
+            for b in &positions[ix + 1..] {
+                expected += (a - b).abs();
+            }
+        }
+        assert_eq!(sum_of_distances(positions), expected);
+        assert_eq!(sum_of_distances(vec![]), 0);
+    }
 }
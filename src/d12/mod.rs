@@ -1,6 +1,9 @@
+use crate::memo::{GridMemo, HashMemo, Memo};
+use crate::params::Params;
+use crate::parse::unsigned_numbers;
 use crate::Solver;
 use core::panic;
-use std::{collections::HashMap, str::Lines};
+use std::str::Lines;
 
 pub struct Solver12;
 
@@ -10,92 +13,115 @@ impl Solver for Solver12 {
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        // naive brute force will take too long.  Instead need some state to limit the search space
-        let mut sum: u64 = 0;
-        let number_matcher = regex::Regex::new(r"\d+").unwrap();
-
-        for line in input_lines {
-            {
-                let (spring_row, numbers) = line.split_once(' ').unwrap();
-
-                let group_sizes: Vec<usize> = number_matcher
-                    .find_iter(numbers)
-                    .map(|n| n.as_str().parse::<usize>().unwrap())
-                    .collect();
-
-                let mut match_cache = MatchCache {
-                    matches: HashMap::new(),
-                };
-
-                let matches_this_row = match_cache.can_start_group_at_or_after_position(
-                    &spring_row.chars().collect::<Vec<char>>(),
-                    &group_sizes,
-                );
-                sum += matches_this_row;
-            }
-        }
-
-        sum.to_string()
+        self.part1_with_params(input_lines, &Params::new())
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        // naive brute force will take too long.  Instead need some state to limit the search space
-        let mut sum: u64 = 0;
-        let number_matcher = regex::Regex::new(r"\d+").unwrap();
-
-        for line in input_lines {
-            let (springs, numbers) = line.split_once(' ').unwrap();
-            let spring_row = format!(
-                "{}?{}?{}?{}?{}",
-                springs, springs, springs, springs, springs
-            );
-            let numbers = format!(
-                "{},{},{},{},{}",
-                numbers, numbers, numbers, numbers, numbers
-            );
+        self.part2_with_params(input_lines, &Params::new())
+    }
 
-            let group_sizes: Vec<usize> = number_matcher
-                .find_iter(&numbers)
-                .map(|n| n.as_str().parse::<usize>().unwrap())
-                .collect();
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve(input_lines, params.get_or("unfold", 1), params)
+    }
 
-            let mut match_cache = MatchCache {
-                matches: HashMap::new(),
-            };
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve(input_lines, params.get_or("unfold", 5), params)
+    }
+}
 
-            let matches_this_row = match_cache.can_start_group_at_or_after_position(
-                &spring_row.chars().collect::<Vec<char>>(),
-                &group_sizes,
-            );
-            sum += matches_this_row
-        }
+// `show_arrangements=N` lists the first N arrangements of each row after the answer, and
+// `arrangement=K` picks out the Kth (from 0).  Both are for debugging small rows.
+fn solve(input_lines: Lines, unfold_factor: usize, params: &Params) -> String {
+    let total = total_arrangements(input_lines.clone(), unfold_factor, count_method(params));
+    let show = params.get_or("show_arrangements", 0);
+    let index: Option<u64> = params.get("arrangement").map(|ix| ix.parse().unwrap());
+
+    let mut output = total.to_string();
+    if show == 0 && index.is_none() {
+        return output;
+    }
 
-        sum.to_string()
+    for line in input_lines {
+        let (springs, group_sizes) = unfold(line, unfold_factor);
+        let arrangements = Arrangements::new(&springs, &group_sizes);
+        output.push_str(&format!("\n{line}"));
+        if let Some(index) = index {
+            let arrangement = arrangements.nth_arrangement(index);
+            output.push_str(&format!(
+                "\n  {index}: {}",
+                arrangement.as_deref().unwrap_or("none")
+            ));
+        }
+        for arrangement in arrangements.take(show) {
+            output.push_str(&format!("\n  {arrangement}"));
+        }
     }
+    output
+}
+
+fn total_arrangements(input_lines: Lines, unfold_factor: usize, method: CountMethod) -> u64 {
+    // naive brute force will take too long.  Instead need some state to limit the search space
+    input_lines
+        .map(|line| {
+            let (springs, group_sizes) = unfold(line, unfold_factor);
+            count_arrangements(&springs, &group_sizes, method)
+        })
+        .sum()
+}
+
+// The row repeated `factor` times, with unknown springs between the copies
+fn unfold(line: &str, factor: usize) -> (Vec<char>, Vec<usize>) {
+    let (springs, numbers) = line.split_once(' ').unwrap();
+    let springs = vec![springs; factor].join("?").chars().collect();
+    let group_sizes = unsigned_numbers::<usize>(numbers)
+        .collect::<Vec<usize>>()
+        .repeat(factor);
+    (springs, group_sizes)
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum CountMethod {
+    // Recursive search, cached in a hash map
+    HashMemo,
+    // Recursive search, cached in a dense table
+    GridMemo,
+    // Fill in the counts for every suffix of the row, from the end backwards
+    BottomUp,
 }
 
-struct MatchCache {
-    matches: HashMap<(usize, usize), u64>,
+fn count_method(params: &Params) -> CountMethod {
+    match params.get("count").unwrap_or("grid") {
+        "hash" => CountMethod::HashMemo,
+        "grid" => CountMethod::GridMemo,
+        "bottom-up" => CountMethod::BottomUp,
+        other => panic!("Unknown count method {other}"),
+    }
 }
 
-impl MatchCache {
-    fn can_start_group_at_or_after_position(
-        &mut self,
-        remaining_springs: &[char],
-        remaining_groups: &[usize],
-    ) -> u64 {
-        // currently this recurses even when we've previously verified the same end of a row can match
-        // we could us a cache instead?
+fn count_arrangements(springs: &[char], group_sizes: &[usize], method: CountMethod) -> u64 {
+    match method {
+        CountMethod::HashMemo => {
+            can_start_group_at_or_after_position(&mut HashMemo::new(), springs, group_sizes)
+        }
+        CountMethod::GridMemo => can_start_group_at_or_after_position(
+            &mut GridMemo::new(springs.len() + 1, group_sizes.len() + 1),
+            springs,
+            group_sizes,
+        ),
+        CountMethod::BottomUp => count_arrangements_bottom_up(springs, group_sizes),
+    }
+}
 
+fn can_start_group_at_or_after_position(
+    cache: &mut impl Memo<(usize, usize), u64>,
+    remaining_springs: &[char],
+    remaining_groups: &[usize],
+) -> u64 {
+    // The count only depends on how much of the row and the groups are left, so cache on those
+    cache.get_or_compute((remaining_springs.len(), remaining_groups.len()), |cache| {
         // Set some local variables for values we keep getting
         let num_remaining_springs = remaining_springs.len();
-        let num_remaining_groups = remaining_groups.len();
         let num_springs_in_remaining_groups = remaining_groups.iter().sum::<usize>();
-        let this_entry = (num_remaining_springs, num_remaining_groups);
-
-        if let Some(number) = self.matches.get(&this_entry) {
-            return *number;
-        }
 
         let remaining_broken_spring_count = remaining_springs.iter().filter(|c| **c == '#').count();
         let remaining_non_working_spring_count =
@@ -105,11 +131,9 @@ impl MatchCache {
         if remaining_groups.is_empty() {
             // If there are no more broken springs, return one match variant
             if remaining_broken_spring_count == 0 {
-                self.matches.insert(this_entry, 1);
                 return 1;
             } else {
                 // There are more broken springs - whoops!  can't match
-                self.matches.insert(this_entry, 0);
                 return 0;
             }
         }
@@ -130,7 +154,6 @@ impl MatchCache {
            // There are more groups to match, but not enough springs of the right type to match them
            (remaining_non_working_spring_count < num_springs_in_remaining_groups )
         {
-            self.matches.insert(this_entry, 0);
             return 0;
         }
 
@@ -138,84 +161,204 @@ impl MatchCache {
             '.' => {
                 // Can't start exactly here - can we start at the next position?
 
-                let val = self.can_start_group_at_or_after_position(
+                can_start_group_at_or_after_position(
+                    cache,
                     &remaining_springs[1..],
                     remaining_groups,
-                );
-
-                self.matches.insert(this_entry, val);
-                val
+                )
             }
             '#' => {
                 // must start a group here if we can
                 if num_remaining_springs == this_group_size {
-                    self.matches.insert(this_entry, 1);
                     return 1;
                 }
 
                 // Working springs in the way
-                if remaining_springs[0..this_group_size]
-                    .iter()
-                    .any(|c| *c == '.') ||
+                if remaining_springs[0..this_group_size].contains(&'.') ||
                     // We can't match here, because there would be too many broken springs
                     remaining_springs[this_group_size] == '#'
                 {
-                    self.matches.insert(this_entry, 0);
                     return 0;
                 }
 
                 // OK - let's try putting the next group here.
-                let val = self.can_start_group_at_or_after_position(
+                can_start_group_at_or_after_position(
+                    cache,
                     &remaining_springs[this_group_size + 1..],
                     &remaining_groups[1..],
-                );
-
-                self.matches.insert(this_entry, val);
-                val
+                )
             }
             '?' => {
                 // Can choose whether to start a group here or not
 
                 if num_remaining_springs == this_group_size {
                     // Exact size match - no more groups to check
-                    self.matches.insert(this_entry, 1);
                     return 1;
                 }
 
                 // Working springs in the way
-                if remaining_springs[0..this_group_size]
-                    .iter()
-                    .any(|c| *c == '.') ||
+                if remaining_springs[0..this_group_size].contains(&'.') ||
 
                  // We can't match exactly here, because there would be too many broken springs
                     remaining_springs[this_group_size] == '#'
                 {
                     // Try the next location
-                    let val = self.can_start_group_at_or_after_position(
+                    return can_start_group_at_or_after_position(
+                        cache,
                         &remaining_springs[1..],
                         remaining_groups,
                     );
-
-                    self.matches.insert(this_entry, val);
-                    return val;
                 }
 
                 // Can start here, or could try the next location.
-                let val = self.can_start_group_at_or_after_position(
+                can_start_group_at_or_after_position(
+                    cache,
                     &remaining_springs[this_group_size + 1..],
                     &remaining_groups[1..],
-                ) + self.can_start_group_at_or_after_position(
+                ) + can_start_group_at_or_after_position(
+                    cache,
                     &remaining_springs[1..],
                     remaining_groups,
-                );
-
-                self.matches.insert(this_entry, val);
-                val
+                )
             }
             _ => panic!("Unexpected character {}", remaining_springs[0]),
         }
+    })
+}
+
+fn count_arrangements_bottom_up(springs: &[char], group_sizes: &[usize]) -> u64 {
+    arrangement_counts(springs, group_sizes)[0][0]
+}
+
+// No working springs in the group, and no broken spring straight after it
+fn group_fits(springs: &[char], group_sizes: &[usize], s: usize, g: usize) -> bool {
+    let group_end = s + group_sizes[g];
+    group_end <= springs.len()
+        && !springs[s..group_end].contains(&'.')
+        && springs.get(group_end) != Some(&'#')
+}
+
+// counts[s][g] is the number of ways of matching groups g.. to springs s..
+fn arrangement_counts(springs: &[char], group_sizes: &[usize]) -> Vec<Vec<u64>> {
+    let num_springs = springs.len();
+    let num_groups = group_sizes.len();
+
+    let mut counts = vec![vec![0_u64; num_groups + 1]; num_springs + 1];
+    counts[num_springs][num_groups] = 1;
+
+    for s in (0..num_springs).rev() {
+        for g in 0..=num_groups {
+            // Leave this spring working, and match everything to the rest of the row
+            if springs[s] != '#' {
+                counts[s][g] += counts[s + 1][g];
+            }
+
+            // Or start the next group here, if it fits
+            if springs[s] != '.' && g < num_groups && group_fits(springs, group_sizes, s, g) {
+                let group_end = s + group_sizes[g];
+                counts[s][g] += counts[(group_end + 1).min(num_springs)][g + 1];
+            }
+        }
+    }
+
+    counts
+}
+
+// Partial arrangements which can't be finished are never explored, so each arrangement takes time
+// linear in the row length
+struct Arrangements<'a> {
+    springs: &'a [char],
+    group_sizes: &'a [usize],
+    counts: Vec<Vec<u64>>,
+    // (next spring, next group, springs so far)
+    stack: Vec<(usize, usize, String)>,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(springs: &'a [char], group_sizes: &'a [usize]) -> Arrangements<'a> {
+        let counts = arrangement_counts(springs, group_sizes);
+        let stack = if counts[0][0] > 0 {
+            vec![(0, 0, String::new())]
+        } else {
+            vec![]
+        };
+
+        Arrangements {
+            springs,
+            group_sizes,
+            counts,
+            stack,
+        }
+    }
+
+    // Without generating the ones before it
+    fn nth_arrangement(&self, mut index: u64) -> Option<String> {
+        if index >= self.counts[0][0] {
+            return None;
+        }
+
+        let (mut s, mut g) = (0, 0);
+        let mut arrangement = String::new();
+
+        while s < self.springs.len() {
+            // Arrangements starting a group here come first
+            if let Some((next_s, placed)) = self.place_group(s, g) {
+                let with_group = self.counts[next_s][g + 1];
+                if index < with_group {
+                    arrangement.push_str(&placed);
+                    (s, g) = (next_s, g + 1);
+                    continue;
+                }
+                index -= with_group;
+            }
+
+            arrangement.push('.');
+            s += 1;
+        }
+
+        Some(arrangement)
+    }
+
+    fn place_group(&self, s: usize, g: usize) -> Option<(usize, String)> {
+        if self.springs[s] == '.'
+            || g == self.group_sizes.len()
+            || !group_fits(self.springs, self.group_sizes, s, g)
+        {
+            return None;
+        }
+
+        let group_end = s + self.group_sizes[g];
+        let mut placed = "#".repeat(self.group_sizes[g]);
+        if group_end < self.springs.len() {
+            placed.push('.');
+        }
+        Some(((group_end + 1).min(self.springs.len()), placed))
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some((s, g, arrangement)) = self.stack.pop() {
+            if s == self.springs.len() {
+                return Some(arrangement);
+            }
+
+            // Pushed in reverse, so that starting a group comes out first
+            if self.springs[s] != '#' && self.counts[s + 1][g] > 0 {
+                self.stack.push((s + 1, g, arrangement.clone() + "."));
+            }
+            if let Some((next_s, placed)) = self.place_group(s, g) {
+                if self.counts[next_s][g + 1] > 0 {
+                    self.stack.push((next_s, g + 1, arrangement + &placed));
+                }
+            }
+        }
+        None
     }
 }
+
 #[cfg(test)]
 mod tests {
     use crate::Solver;
@@ -249,4 +392,210 @@ mod tests {
     fn single_line_p2() {
         super::Solver12.part2("..?.????#?????????? 1,1,1,1,1,4".lines());
     }
+
+    #[test]
+    fn unfold_param() {
+        use crate::params::Params;
+
+        let sample_input = "???.### 1,1,3
+.??..??...?##. 1,1,3";
+        let mut params = Params::new();
+        params.insert("unfold=2");
+        // The second row has 4 arrangements, then 32 unfolded twice
+        assert_eq!(
+            super::Solver12.part1_with_params(sample_input.lines(), &params),
+            "33"
+        );
+        params.insert("unfold=5");
+        assert_eq!(
+            super::Solver12.part1_with_params(sample_input.lines(), &params),
+            (1 + 16384).to_string()
+        );
+    }
+
+    #[test]
+    fn count_param() {
+        use crate::params::Params;
+
+        let sample_input = "???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1";
+        for method in ["hash", "grid", "bottom-up"] {
+            let mut params = Params::new();
+            params.insert(&format!("count={method}"));
+            assert_eq!(
+                super::Solver12.part2_with_params(sample_input.lines(), &params),
+                "525152"
+            );
+        }
+    }
+
+    #[test]
+    fn arrangement_params() {
+        use crate::params::Params;
+
+        let sample_input = "???.### 1,1,3
+.??..??...?##. 1,1,3";
+        let mut params = Params::new();
+        params.insert("show_arrangements=2");
+        assert_eq!(
+            super::Solver12.part1_with_params(sample_input.lines(), &params),
+            "5
+???.### 1,1,3
+  #.#.###
+.??..??...?##. 1,1,3
+  .#...#....###.
+  .#....#...###."
+        );
+
+        let mut params = Params::new();
+        params.insert("arrangement=3");
+        assert_eq!(
+            super::Solver12.part1_with_params(sample_input.lines(), &params),
+            "5
+???.### 1,1,3
+  3: none
+.??..??...?##. 1,1,3
+  3: ..#...#...###."
+        );
+    }
+
+    #[test]
+    fn enumerate_arrangements() {
+        use super::Arrangements;
+
+        let springs = "?###????????".chars().collect::<Vec<char>>();
+        let groups = [3, 2, 1];
+        let arrangements: Vec<String> = Arrangements::new(&springs, &groups).collect();
+
+        assert_eq!(arrangements.len(), 10);
+        assert_eq!(arrangements[0], ".###.##.#...");
+        assert_eq!(arrangements[9], ".###....##.#");
+
+        // Lazily, from a row with far too many to list
+        let (springs, groups) = super::unfold("?###???????? 3,2,1", 5);
+        let mut arrangements = Arrangements::new(&springs, &groups);
+        let first = arrangements.next().unwrap();
+        assert_eq!(first.len(), springs.len());
+        assert_eq!(arrangements.nth_arrangement(0), Some(first));
+        assert!(arrangements.nth_arrangement(506249).is_some());
+        assert_eq!(arrangements.nth_arrangement(506250), None);
+    }
+
+    #[test]
+    fn enumerated_arrangements_match_counts() {
+        use super::{count_arrangements, Arrangements, CountMethod};
+        use std::collections::HashSet;
+
+        let group_lists: [&[usize]; 8] =
+            [&[], &[1], &[2], &[3], &[1, 1], &[1, 2], &[2, 1], &[1, 1, 1]];
+
+        // Every row of up to 7 springs
+        let mut rows = vec![String::new()];
+        for _ in 0..7 {
+            let longer: Vec<String> = rows
+                .iter()
+                .filter(|row| row.len() == rows.last().unwrap().len())
+                .flat_map(|row| ['.', '#', '?'].map(|c| format!("{row}{c}")))
+                .collect();
+            rows.extend(longer);
+        }
+
+        for row in &rows {
+            let springs = row.chars().collect::<Vec<char>>();
+            for groups in group_lists {
+                let arrangements: Vec<String> = Arrangements::new(&springs, groups).collect();
+                let expected = count_arrangements(&springs, groups, CountMethod::HashMemo);
+                assert_eq!(arrangements.len() as u64, expected, "{row} {groups:?}");
+
+                let distinct: HashSet<&String> = arrangements.iter().collect();
+                assert_eq!(distinct.len(), arrangements.len());
+
+                for (ix, arrangement) in arrangements.iter().enumerate() {
+                    // Consistent with the row, with the right groups
+                    assert!(springs
+                        .iter()
+                        .zip(arrangement.chars())
+                        .all(|(s, a)| *s == '?' || *s == a));
+                    let found: Vec<usize> = arrangement
+                        .split('.')
+                        .filter(|g| !g.is_empty())
+                        .map(|g| g.len())
+                        .collect();
+                    assert_eq!(found, groups);
+
+                    let generator = Arrangements::new(&springs, groups);
+                    assert_eq!(
+                        generator.nth_arrangement(ix as u64).as_ref(),
+                        Some(arrangement)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn count_methods_agree() {
+        use super::{count_arrangements, CountMethod};
+
+        let rows = [
+            ("???.###", vec![1, 1, 3], 1),
+            (".??..??...?##.", vec![1, 1, 3], 4),
+            ("?#?#?#?#?#?#?#?", vec![1, 3, 1, 6], 1),
+            ("????.#...#...", vec![4, 1, 1], 1),
+            ("????.######..#####.", vec![1, 6, 5], 4),
+            ("?###????????", vec![3, 2, 1], 10),
+            (
+                "?###??????????###??????????###??????????###??????????###????????",
+                [3, 2, 1].repeat(5),
+                506250,
+            ),
+            ("?#?", vec![1], 1),
+            ("", vec![], 1),
+            ("#", vec![], 0),
+        ];
+
+        for (springs, groups, expected) in rows {
+            let springs = springs.chars().collect::<Vec<char>>();
+            for method in [
+                CountMethod::HashMemo,
+                CountMethod::GridMemo,
+                CountMethod::BottomUp,
+            ] {
+                assert_eq!(
+                    count_arrangements(&springs, &groups, method),
+                    expected,
+                    "{springs:?} {groups:?} {method:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "timing comparison; run with --ignored --nocapture"]
+    fn count_method_speeds() {
+        use super::{count_arrangements, CountMethod};
+
+        let input_text = std::fs::read_to_string("src/d12/input.txt").unwrap();
+        let rows = input_text
+            .lines()
+            .map(|line| super::unfold(line, 5))
+            .collect::<Vec<_>>();
+
+        for method in [
+            CountMethod::HashMemo,
+            CountMethod::GridMemo,
+            CountMethod::BottomUp,
+        ] {
+            let start = std::time::Instant::now();
+            let total: u64 = rows
+                .iter()
+                .map(|(springs, groups)| count_arrangements(springs, groups, method))
+                .sum();
+            println!("{method:?}: {total} in {:?}", start.elapsed());
+        }
+    }
 }
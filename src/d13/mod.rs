@@ -1,240 +1,118 @@
+use crate::params::Params;
+use crate::parse::sections;
 use crate::Solver;
-use itertools::Itertools;
-use std::{collections::HashMap, fmt::Debug, str::Lines, vec};
+use std::str::Lines;
 pub struct Solver13;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 enum Dir {
+    // About a line between two rows
     Vertical,
+    // About a line between two columns
     Horizontal,
 }
 
-#[derive(PartialEq, Clone, Copy, Eq, Hash)]
-enum NodeVal {
-    Hash,
-    Dot,
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Reflection {
+    dir: Dir,
+    // Rows above, or columns to the left of, the line of reflection
+    position: usize,
+    smudges: u32,
 }
 
-impl Debug for NodeVal {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Hash => write!(f, "#"),
-            Self::Dot => write!(f, "."),
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.dir {
+            Dir::Vertical => self.position * 100,
+            Dir::Horizontal => self.position,
         }
     }
 }
 
-impl NodeVal {
-    fn from_char(c: char) -> NodeVal {
-        match c {
-            '#' => NodeVal::Hash,
-            '.' => NodeVal::Dot,
-            _ => panic!("Invalid node value"),
-        }
-    }
-
-    fn other_type(&self) -> NodeVal {
-        match self {
-            Self::Hash => Self::Dot,
-            Self::Dot => Self::Hash,
-        }
-    }
+// Each row and column packed into the bits of an integer, '#' being 1
+struct Pattern {
+    rows: Vec<u64>,
+    columns: Vec<u64>,
 }
 
-impl Solver for Solver13 {
-    fn day_number(&self) -> u32 {
-        13
-    }
-
-    fn part1(&self, input_lines: Lines) -> String {
-        let mut symmetry_sum = 0;
-
-        let node_maps: Vec<Vec<Vec<NodeVal>>> = input_lines
-            .map(|line| {
-                line.chars()
-                    .map(NodeVal::from_char)
-                    .collect::<Vec<NodeVal>>()
-            }) // convert each line to a vector of chars
-            .group_by(|line| line.is_empty()) // group the lines into groups of empty and non-empty lines
-            .into_iter()
-            .filter_map(|(empty, map)| if empty { None } else { Some(map.collect()) }) // drop empty lines
-            .collect();
-
-        for node_map in node_maps {
-            symmetry_sum += find_only_symmetry_values(&node_map).unwrap();
-        }
-
-        symmetry_sum.to_string()
-    }
-
-    fn part2(&self, input_lines: Lines) -> String {
-        // naive version - just test for changing each nodeacter in each map
-
-        let mut symmetry_sum = 0;
-
-        let node_maps: Vec<Vec<Vec<NodeVal>>> = input_lines
-            .map(|line| {
-                line.chars()
-                    .map(NodeVal::from_char)
-                    .collect::<Vec<NodeVal>>()
-            }) // convert each line to a vector of chars
-            .group_by(|line| line.is_empty()) // group the lines into groups of empty and non-empty lines
-            .into_iter()
-            .filter_map(|(empty, map)| if empty { None } else { Some(map.collect()) }) // drop empty lines
-            .collect();
-
-        for node_map in node_maps {
-            let original_symmetry_line = find_horiz_or_vert_sym_values(&node_map).next().unwrap();
-
-            let mut found_symmetry = false;
-
-            for (y, row) in node_map.iter().enumerate() {
-                for (x, c) in row.iter().enumerate() {
-                    let mut altered_node_map = node_map.clone();
-                    altered_node_map[y][x] = c.other_type();
-
-                    if any_matches(&altered_node_map, x, y) {
-                        if let Some(val) =
-                            find_new_symmetry_values(&altered_node_map, original_symmetry_line)
-                        {
-                            symmetry_sum += val;
-                            found_symmetry = true;
-                            break;
-                        }
+impl Pattern {
+    fn parse(lines: &[&str]) -> Pattern {
+        let width = lines[0].len();
+        assert!(width <= 64 && lines.len() <= 64, "Pattern too big");
+
+        let mut rows = vec![0; lines.len()];
+        let mut columns = vec![0; width];
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '#' => {
+                        rows[y] |= 1 << x;
+                        columns[x] |= 1 << y;
                     }
-                }
-                if found_symmetry {
-                    break;
+                    '.' => (),
+                    _ => panic!("Invalid node value"),
                 }
             }
         }
 
-        symmetry_sum.to_string()
+        Pattern { rows, columns }
     }
-}
 
-fn any_matches(node_map: &Vec<Vec<NodeVal>>, x: usize, y: usize) -> bool {
-    if node_map.iter().filter(|row| **row == node_map[y]).count() > 1 {
-        return true;
+    fn reflections(&self) -> impl Iterator<Item = Reflection> + '_ {
+        reflections_of(&self.rows, Dir::Vertical)
+            .chain(reflections_of(&self.columns, Dir::Horizontal))
     }
 
-    let mut translated_node_map = vec![vec![NodeVal::Dot; node_map.len()]; node_map[0].len()];
-
-    for (y_ix, row) in node_map.iter().enumerate() {
-        for (x_ix, c) in row.iter().enumerate() {
-            translated_node_map[x_ix][y_ix] = *c;
-        }
+    fn reflections_with_smudges(&self, smudges: u32) -> impl Iterator<Item = Reflection> + '_ {
+        self.reflections().filter(move |r| r.smudges == smudges)
     }
-
-    translated_node_map
-        .iter()
-        .filter(|row| **row == translated_node_map[x])
-        .count()
-        > 1
-}
-fn find_only_symmetry_values(node_map: &Vec<Vec<NodeVal>>) -> Option<usize> {
-    find_symmetry_values(node_map, |_| true)
 }
 
-fn find_symmetry_values<P>(node_map: &Vec<Vec<NodeVal>>, filter: P) -> Option<usize>
-where
-    P: FnMut(&(usize, Dir)) -> bool,
-{
-    find_horiz_or_vert_sym_values(node_map)
-        .filter(filter)
-        .map(
-            |(val, dir)| {
-                if dir == Dir::Vertical {
-                    val * 100
-                } else {
-                    val
-                }
-            },
-        )
-        .next()
+// Each pair of rows which the line maps onto each other adds the number of bits where they differ
+fn reflections_of(lines: &[u64], dir: Dir) -> impl Iterator<Item = Reflection> + '_ {
+    (1..lines.len()).map(move |position| {
+        let before = lines[..position].iter().rev();
+        let after = &lines[position..];
+        let smudges = before.zip(after).map(|(a, b)| (a ^ b).count_ones()).sum();
+        Reflection {
+            dir,
+            position,
+            smudges,
+        }
+    })
 }
 
-fn find_new_symmetry_values(
-    node_map: &Vec<Vec<NodeVal>>,
-    orig_symmetry_value: (usize, Dir),
-) -> Option<usize> {
-    let filter = |symm_val: &(usize, Dir)| *symm_val != orig_symmetry_value;
-    find_symmetry_values(node_map, filter)
-}
+impl Solver for Solver13 {
+    fn day_number(&self) -> u32 {
+        13
+    }
 
-fn find_horiz_or_vert_sym_values(
-    node_map: &Vec<Vec<NodeVal>>,
-) -> impl Iterator<Item = (usize, Dir)> {
-    let max_x = node_map[0].len() - 1;
-    let max_y = node_map.len() - 1;
-    let mut translated_node_map = vec![vec![NodeVal::Dot; max_y + 1]; max_x + 1];
+    fn part1(&self, input_lines: Lines) -> String {
+        self.part1_with_params(input_lines, &Params::new())
+    }
 
-    for (y_ix, row) in node_map.iter().enumerate() {
-        for (x_ix, c) in row.iter().enumerate() {
-            translated_node_map[x_ix][y_ix] = *c;
-        }
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
     }
 
-    vec![
-        find_symmetries(node_map, Dir::Vertical),
-        find_symmetries(&translated_node_map, Dir::Horizontal),
-    ]
-    .into_iter()
-    .flatten()
-}
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        summarize(input_lines, params.get_or("smudges", 0)).to_string()
+    }
 
-fn eq_dist_from_symmetry_line(this_line: i32, other_line: i32, symmetry_start_line: i32) -> bool {
-    (this_line - symmetry_start_line) == (symmetry_start_line - 1 - other_line)
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        summarize(input_lines, params.get_or("smudges", 1)).to_string()
+    }
 }
-fn find_symmetries(node_map: &Vec<Vec<NodeVal>>, direction: Dir) -> Vec<(usize, Dir)> {
-    let mut symmetries = vec![];
-    let max_y = node_map.len() - 1;
-    let mut lines_hash: HashMap<&Vec<NodeVal>, Vec<usize>> = HashMap::new();
 
-    let mut start_of_symmetry: Option<usize> = None;
-
-    for (ix, line) in node_map.iter().enumerate() {
-        let matching = lines_hash.get_mut(line);
-
-        match matching {
-            None => {
-                start_of_symmetry = None;
-                lines_hash.insert(line, vec![ix]);
-            }
-            Some(matching) => {
-                let mut found_good_match = false;
-
-                for prev_match in matching.iter() {
-                    if !start_of_symmetry.is_some_and(|start| {
-                        eq_dist_from_symmetry_line(ix as i32, *prev_match as i32, start as i32)
-                    }) {
-                        // Either this is the first match, or the match was not a mirror image
-                        if ix != prev_match + 1 {
-                            // not a good starting match
-                            continue;
-                        }
-
-                        // We'll only get to the here line if there were no other good matches, so restart the symmetry here
-                        start_of_symmetry = Some(ix);
-                    }
-
-                    found_good_match = true;
-
-                    if (ix == 0) || (*prev_match == 0) || (ix == max_y) || (*prev_match == max_y) {
-                        symmetries.push((start_of_symmetry.unwrap(), direction));
-                        start_of_symmetry = None;
-                    }
-                    break;
-                }
-
-                if !found_good_match {
-                    start_of_symmetry = None;
-                }
-                matching.push(ix);
-            }
-        }
-    }
-    symmetries
+fn summarize(input_lines: Lines, smudges: u32) -> usize {
+    sections(input_lines)
+        .map(|lines| {
+            Pattern::parse(&lines)
+                .reflections_with_smudges(smudges)
+                .map(|r| r.summary())
+                .sum::<usize>()
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -280,6 +158,90 @@ mod tests {
         assert_eq!(super::Solver13.part2(sample_input.lines()), "400");
     }
 
+    #[test]
+    fn smudge_counts() {
+        use super::{Dir, Pattern, Reflection};
+
+        let lines = [
+            "#.##..##.",
+            "..#.##.#.",
+            "##......#",
+            "##......#",
+            "..#.##.#.",
+            "..##..##.",
+            "#.#.##.#.",
+        ];
+        let pattern = Pattern::parse(&lines);
+
+        let perfect: Vec<Reflection> = pattern.reflections_with_smudges(0).collect();
+        assert_eq!(
+            perfect,
+            vec![Reflection {
+                dir: Dir::Horizontal,
+                position: 5,
+                smudges: 0
+            }]
+        );
+        let one_smudge: Vec<Reflection> = pattern.reflections_with_smudges(1).collect();
+        assert_eq!(
+            one_smudge,
+            vec![Reflection {
+                dir: Dir::Vertical,
+                position: 3,
+                smudges: 1
+            }]
+        );
+
+        // Check every count against comparing the cells one by one
+        let cells: Vec<Vec<char>> = lines.iter().map(|l| l.chars().collect()).collect();
+        for reflection in pattern.reflections() {
+            let mut expected = 0;
+            for (y, row) in cells.iter().enumerate() {
+                for (x, c) in row.iter().enumerate() {
+                    let (mirror_x, mirror_y) = match reflection.dir {
+                        Dir::Vertical => (x as i64, 2 * reflection.position as i64 - 1 - y as i64),
+                        Dir::Horizontal => {
+                            (2 * reflection.position as i64 - 1 - x as i64, y as i64)
+                        }
+                    };
+                    let mirrored = cells
+                        .get(mirror_y as usize)
+                        .and_then(|row| row.get(mirror_x as usize));
+                    if mirror_x >= 0 && mirror_y >= 0 && mirrored.is_some_and(|m| m != c) {
+                        expected += 1;
+                    }
+                }
+            }
+            // Each mismatched pair is seen from both sides
+            assert_eq!(reflection.smudges * 2, expected, "{reflection:?}");
+        }
+    }
+
+    #[test]
+    fn smudges_param() {
+        use crate::params::Params;
+
+        let sample_input = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.";
+        let mut params = Params::new();
+        // Only the line after the first column is two cells off
+        params.insert("smudges=2");
+        assert_eq!(
+            super::Solver13.part2_with_params(sample_input.lines(), &params),
+            "1"
+        );
+        params.insert("smudges=3");
+        assert_eq!(
+            super::Solver13.part2_with_params(sample_input.lines(), &params),
+            "0"
+        );
+    }
+
     #[test]
     fn single_map() {
         let sample_input = "##..####.
@@ -293,6 +255,6 @@ mod tests {
 #.##.###.
 #.##.###.
 #....##..";
-        super::Solver13.part1(sample_input.lines());
+        assert_eq!(super::Solver13.part1(sample_input.lines()), "800");
     }
 }
@@ -1,36 +1,10 @@
-use core::panic;
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc, str::Lines};
-
-use itertools::Itertools;
+use std::{collections::HashMap, fmt::Display, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 pub struct Solver14;
 
-#[derive(PartialEq, Debug, PartialOrd, Ord, Eq, Copy, Clone, Hash)]
-enum Rock {
-    Square = 0,
-    Round = 1,
-    None = 2,
-}
-
-impl Rock {
-    fn to_char(self) -> char {
-        match self {
-            Rock::Square => '#',
-            Rock::Round => 'O',
-            Rock::None => '.',
-        }
-    }
-}
-
-impl Display for Rock {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_char())
-    }
-}
-
-type Location = Rc<RefCell<Rock>>;
-
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum Dir {
     North,
     East,
@@ -38,217 +12,209 @@ enum Dir {
     West,
 }
 
+impl Dir {
+    fn from_char(c: char) -> Dir {
+        match c {
+            'N' => Dir::North,
+            'E' => Dir::East,
+            'S' => Dir::South,
+            'W' => Dir::West,
+            _ => panic!("Unknown direction {c}"),
+        }
+    }
+}
+
 impl Solver for Solver14 {
     fn day_number(&self) -> u32 {
         14
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let mut rows_of_rocks: Vec<Vec<Location>> = vec![];
-        let mut cols_of_rocks: Vec<Vec<Location>> = vec![];
-
-        for (y, line) in input_lines.enumerate() {
-            if rows_of_rocks.get(y).is_none() {
-                rows_of_rocks.push(vec![]);
-            }
-            for (x, c) in line.chars().enumerate() {
-                if cols_of_rocks.get(x).is_none() {
-                    cols_of_rocks.push(vec![]);
-                }
-                let location = Rc::new(RefCell::new(match c {
-                    '.' => Rock::None,
-                    '#' => Rock::Square,
-                    'O' => Rock::Round,
-                    _ => panic!("Invalid input"),
-                }));
+        self.part1_with_params(input_lines, &Params::new())
+    }
 
-                rows_of_rocks[y].push(location.clone());
-                cols_of_rocks[x].push(location);
-            }
-        }
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
+    }
 
-        roll_balls(&mut rows_of_rocks, &mut cols_of_rocks, Dir::North);
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        load_after_tilts(input_lines, params, "N", 1)
+    }
 
-        calc_north_weight(&rows_of_rocks).to_string()
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        load_after_tilts(input_lines, params, "NWSE", 1000000000)
     }
+}
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut rows_of_rocks: Vec<Vec<Location>> = vec![];
-        let mut cols_of_rocks: Vec<Vec<Location>> = vec![];
+fn load_after_tilts(input_lines: Lines, params: &Params, tilts: &str, cycles: usize) -> String {
+    let tilts: Vec<Dir> = params
+        .get("tilts")
+        .unwrap_or(tilts)
+        .chars()
+        .map(Dir::from_char)
+        .collect();
+    let cycles = params.get_or("cycles", cycles);
+    let side = Dir::from_char(params.get_or("load", 'N'));
 
-        for (y, line) in input_lines.enumerate() {
-            if rows_of_rocks.get(y).is_none() {
-                rows_of_rocks.push(vec![]);
-            }
+    let mut board = Board::parse(input_lines);
+    board.spin(&tilts, cycles);
+    board.load(side).to_string()
+}
+
+// A bit for each column in a mask for each row
+#[derive(Debug, Clone, PartialEq)]
+struct Board {
+    width: usize,
+    round: Vec<u128>,
+    square: Vec<u128>,
+}
+
+impl Board {
+    fn parse(input_lines: Lines) -> Board {
+        let mut width = 0;
+        let mut round = vec![];
+        let mut square = vec![];
+
+        for line in input_lines {
+            width = line.len();
+            assert!(width <= 128, "Board too wide");
+
+            let (mut round_row, mut square_row) = (0, 0);
             for (x, c) in line.chars().enumerate() {
-                if cols_of_rocks.get(x).is_none() {
-                    cols_of_rocks.push(vec![]);
-                }
-                let location = Rc::new(RefCell::new(match c {
-                    '.' => Rock::None,
-                    '#' => Rock::Square,
-                    'O' => Rock::Round,
+                match c {
+                    '.' => (),
+                    '#' => square_row |= 1 << x,
+                    'O' => round_row |= 1 << x,
                     _ => panic!("Invalid input"),
-                }));
-
-                rows_of_rocks[y].push(location.clone());
-                cols_of_rocks[x].push(location);
+                }
             }
+            round.push(round_row);
+            square.push(square_row);
         }
 
-        let mut map_history_hashmap: HashMap<String, u32> = HashMap::new();
-        let mut map_history_vec: Vec<String> = vec![];
-
-        for ii in 0..1000000000 {
-            spin(&mut rows_of_rocks, &mut cols_of_rocks);
-
-            let str_representation = rows_to_string(&rows_of_rocks);
-            let start_of_loop = map_history_hashmap.insert(str_representation.clone(), ii);
-            map_history_vec.push(str_representation);
-
-            if let Some(start_of_loop_ix) = start_of_loop {
-                let remaining = (1000000000 - ii - 1) % (ii - start_of_loop_ix);
-                let end_version_ix = start_of_loop_ix + remaining;
-                let previously_found_string = &map_history_vec[end_version_ix as usize];
-                let previously_found_map = previously_found_string
-                    .chars()
-                    .chunks(rows_of_rocks[0].len())
-                    .into_iter()
-                    .map(|row| {
-                        row.map(|c| {
-                            Rc::new(RefCell::new(match c {
-                                '.' => Rock::None,
-                                '#' => Rock::Square,
-                                'O' => Rock::Round,
-                                _ => panic!("Invalid input"),
-                            }))
-                        })
-                        .collect::<Vec<Rc<RefCell<Rock>>>>()
-                    })
-                    .collect::<Vec<Vec<Rc<RefCell<Rock>>>>>();
-                return calc_north_weight(&previously_found_map).to_string();
-            }
+        Board {
+            width,
+            round,
+            square,
         }
-        calc_north_weight(&rows_of_rocks).to_string()
     }
-}
 
-fn spin(rows_of_rocks: &mut Vec<Vec<Location>>, cols_of_rocks: &mut Vec<Vec<Location>>) {
-    roll_balls(rows_of_rocks, cols_of_rocks, Dir::North);
-    roll_balls(rows_of_rocks, cols_of_rocks, Dir::West);
-    roll_balls(rows_of_rocks, cols_of_rocks, Dir::South);
-    roll_balls(rows_of_rocks, cols_of_rocks, Dir::East);
-}
+    fn height(&self) -> usize {
+        self.round.len()
+    }
 
-fn rows_to_string(rows: &[Vec<Location>]) -> String {
-    rows.iter()
-        .flat_map(|row| row.iter().map(|l| l.borrow().to_char()))
-        .collect::<String>()
-}
+    fn free(&self, y: usize) -> u128 {
+        let all = if self.width == 128 {
+            u128::MAX
+        } else {
+            (1 << self.width) - 1
+        };
+        all & !(self.round[y] | self.square[y])
+    }
 
-fn calc_north_weight(rows: &[Vec<Location>]) -> u32 {
-    let num_rows = rows.len();
-    rows.iter()
-        .enumerate()
-        .map(|(row_ix, row)| {
-            row.iter().filter(|l| *l.borrow() == Rock::Round).count() as u32
-                * (num_rows - row_ix) as u32
-        })
-        .sum()
-}
+    // Each round moves every rock which has space one step, until none can move
+    fn tilt(&mut self, dir: Dir) {
+        match dir {
+            Dir::North | Dir::South => loop {
+                let mut moved = false;
+                for y in 1..self.height() {
+                    let (from, to) = if dir == Dir::North {
+                        (y, y - 1)
+                    } else {
+                        (self.height() - 1 - y, self.height() - y)
+                    };
+                    let moving = self.round[from] & self.free(to);
+                    self.round[from] &= !moving;
+                    self.round[to] |= moving;
+                    moved |= moving != 0;
+                }
+                if !moved {
+                    break;
+                }
+            },
+            Dir::East | Dir::West => {
+                for y in 0..self.height() {
+                    loop {
+                        let free = self.free(y);
+                        let moving = if dir == Dir::West {
+                            self.round[y] & (free << 1)
+                        } else {
+                            self.round[y] & (free >> 1)
+                        };
+                        if moving == 0 {
+                            break;
+                        }
+                        let moved = if dir == Dir::West {
+                            moving >> 1
+                        } else {
+                            moving << 1
+                        };
+                        self.round[y] = (self.round[y] & !moving) | moved;
+                    }
+                }
+            }
+        }
+    }
 
-fn sort_rocks(
-    rocks: &Vec<Rc<RefCell<Rock>>>,
-    roll_back_to: usize,
-    first_rock: (Rock, usize),
-    second_rock: (Rock, usize),
-) {
-    update_rocks(rocks, roll_back_to, first_rock.1, first_rock.0);
-    update_rocks(
-        rocks,
-        roll_back_to + first_rock.1,
-        second_rock.1,
-        second_rock.0,
-    );
-}
+    // The round rocks soon fall into a loop of positions, so the cycles after that are skipped
+    fn spin(&mut self, tilts: &[Dir], cycles: usize) {
+        let mut seen: HashMap<Vec<u128>, usize> = HashMap::new();
+        let mut history: Vec<Vec<u128>> = vec![];
 
-fn update_rocks(rocks: &Vec<Rc<RefCell<Rock>>>, start_ix: usize, num: usize, rock_type: Rock) {
-    let start_ix = start_ix;
-    let finish_ix = start_ix + num;
-    for rock in &rocks[start_ix..finish_ix] {
-        *(**rock).borrow_mut() = rock_type;
+        for cycle in 0..cycles {
+            if let Some(loop_start) = seen.insert(self.round.clone(), cycle) {
+                let loop_length = cycle - loop_start;
+                self.round = history[loop_start + (cycles - loop_start) % loop_length].clone();
+                return;
+            }
+            history.push(self.round.clone());
+
+            for dir in tilts {
+                self.tilt(*dir);
+            }
+        }
     }
-}
 
-fn roll_balls(rows: &mut Vec<Vec<Location>>, cols: &mut Vec<Vec<Location>>, dir: Dir) {
-    let (working_map, reverse) = match dir {
-        Dir::North => (&cols, false),
-        Dir::East => (&rows, true),
-        Dir::South => (&cols, true),
-        Dir::West => (&rows, false),
-    };
-
-    for line in working_map.iter() {
-        let mut last_rock_square_or_start = true;
-        let mut rounds_since_last_square: usize = 0;
-        let mut nones_since_last_square: usize = 0;
-        let mut location_to_roll_back_to: usize = 0;
-
-        for loc_ix in 0..line.len() {
-            match *line[loc_ix].borrow() {
-                Rock::Square => {
-                    if !last_rock_square_or_start {
-                        // this is a square, but the last wasn't.  We need to roll the balls
-                        if reverse {
-                            sort_rocks(
-                                line,
-                                location_to_roll_back_to,
-                                (Rock::None, nones_since_last_square),
-                                (Rock::Round, rounds_since_last_square),
-                            );
+    // Distance from the opposite side, counting the row or column at that side as 1
+    fn load(&self, side: Dir) -> usize {
+        let height = self.height();
+        let mut load = 0;
+        for (y, row) in self.round.iter().enumerate() {
+            load += match side {
+                Dir::North => row.count_ones() as usize * (height - y),
+                Dir::South => row.count_ones() as usize * (y + 1),
+                Dir::West | Dir::East => (0..self.width)
+                    .filter(|x| row & (1 << x) != 0)
+                    .map(|x| {
+                        if side == Dir::West {
+                            self.width - x
                         } else {
-                            sort_rocks(
-                                line,
-                                location_to_roll_back_to,
-                                (Rock::Round, rounds_since_last_square),
-                                (Rock::None, nones_since_last_square),
-                            );
+                            x + 1
                         }
-                    };
+                    })
+                    .sum(),
+            };
+        }
+        load
+    }
+}
 
-                    rounds_since_last_square = 0;
-                    nones_since_last_square = 0;
-                    last_rock_square_or_start = true;
-                    location_to_roll_back_to = loc_ix + 1;
-                }
-                Rock::Round => {
-                    rounds_since_last_square += 1;
-                    last_rock_square_or_start = false;
-                }
-                Rock::None => {
-                    nones_since_last_square += 1;
-                    last_rock_square_or_start = false;
-                }
+impl Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height() {
+            for x in 0..self.width {
+                let c = if self.round[y] & (1 << x) != 0 {
+                    'O'
+                } else if self.square[y] & (1 << x) != 0 {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
             }
+            writeln!(f)?;
         }
-        if !last_rock_square_or_start {
-            // finished the row, and have rocks to sort
-            if reverse {
-                sort_rocks(
-                    line,
-                    location_to_roll_back_to,
-                    (Rock::None, nones_since_last_square),
-                    (Rock::Round, rounds_since_last_square),
-                );
-            } else {
-                sort_rocks(
-                    line,
-                    location_to_roll_back_to,
-                    (Rock::Round, rounds_since_last_square),
-                    (Rock::None, nones_since_last_square),
-                );
-            }
-        };
+        Ok(())
     }
 }
 
@@ -284,4 +250,104 @@ O.#..O.#.#
 #OO..#....";
         assert_eq!(super::Solver14.part2(sample_input.lines()), "64");
     }
+
+    const SAMPLE_INPUT: &str = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+
+    #[test]
+    fn spin_cycles() {
+        use super::{Board, Dir};
+
+        // The positions given in the puzzle after each of the first three cycles
+        let after_cycles = [
+            ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+",
+            ".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#..OO###..
+#.OOO#...O
+",
+            ".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#...O###.O
+#.OOO#...O
+",
+        ];
+
+        let spin = [Dir::North, Dir::West, Dir::South, Dir::East];
+        for (ix, expected) in after_cycles.iter().enumerate() {
+            let mut board = Board::parse(SAMPLE_INPUT.lines());
+            board.spin(&spin, ix + 1);
+            assert_eq!(board.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn tilts_and_loads() {
+        use super::{Board, Dir};
+
+        let mut board = Board::parse("O.#.O\n.O..O\n#...O".lines());
+        board.tilt(Dir::East);
+        assert_eq!(board.to_string(), ".O#.O\n...OO\n#...O\n");
+        board.tilt(Dir::South);
+        assert_eq!(board.to_string(), "..#.O\n....O\n#O.OO\n");
+        board.tilt(Dir::West);
+        assert_eq!(board.to_string(), "..#O.\nO....\n#OOO.\n");
+        board.tilt(Dir::North);
+        assert_eq!(board.to_string(), "OO#O.\n..OO.\n#....\n");
+
+        assert_eq!(board.load(Dir::North), 3 * 3 + 2 * 2);
+        assert_eq!(board.load(Dir::South), 3 + 2 * 2);
+        assert_eq!(board.load(Dir::West), (5 + 4 + 2) + (3 + 2));
+        assert_eq!(board.load(Dir::East), (1 + 2 + 4) + (3 + 4));
+    }
+
+    #[test]
+    fn tilt_params() {
+        use crate::params::Params;
+
+        // One cycle of the spin, so the first board of `spin_cycles`
+        let mut params = Params::new();
+        params.insert("cycles=1");
+        assert_eq!(
+            super::Solver14.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "87"
+        );
+
+        // Tilting south first makes no difference to where the rocks end up
+        params.insert("tilts=SN");
+        assert_eq!(
+            super::Solver14.part1_with_params(SAMPLE_INPUT.lines(), &params),
+            "136"
+        );
+    }
 }
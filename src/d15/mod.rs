@@ -1,32 +1,9 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, str::Lines};
+use std::{collections::HashMap, fmt::Display, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 pub struct Solver15;
 
-#[derive(Clone)]
-struct LensBox {
-    lens_map: HashMap<String, Rc<RefCell<Lens>>>,
-    ordered_lenses: Vec<Rc<RefCell<Lens>>>,
-}
-
-impl LensBox {
-    fn new() -> Self {
-        Self {
-            lens_map: HashMap::new(),
-            ordered_lenses: Vec::new(),
-        }
-    }
-}
-
-enum LensBoxAction {
-    Insert(Lens),
-    Remove,
-}
-struct Lens {
-    name: String,
-    power: Option<usize>,
-}
-
 impl Solver for Solver15 {
     fn day_number(&self) -> u32 {
         15
@@ -37,79 +14,196 @@ impl Solver for Solver15 {
             .next()
             .unwrap()
             .split(',')
-            .map(|s| {
-                s.chars()
-                    .fold(0, |acc, c| ((acc + c as u8 as usize) * 17) % 256)
-            })
+            .map(hash)
             .sum::<usize>()
             .to_string()
     }
 
-    fn part2(&self, mut input_lines: Lines) -> String {
-        let mut lens_boxes: Vec<LensBox> = vec![LensBox::new(); 256];
-
-        for instruction in input_lines.next().unwrap().split(',') {
-            let mut parts = instruction.split(|c| c == '=' || c == '-');
-            let label = parts.next().unwrap().to_string();
-            let box_ix = label
-                .chars()
-                .fold(0, |acc, c| ((acc + c as u32 % 256) * 17) % 256)
-                as usize;
-            let action = match parts.next().unwrap().parse::<usize>().ok() {
-                Some(val) => LensBoxAction::Insert(Lens {
-                    name: label.to_string(),
-                    power: Some(val),
-                }),
-                None => LensBoxAction::Remove,
-            };
-
-            let target_box = &mut lens_boxes[box_ix];
-
-            // Optimized over matching (action, lens_map.<some operation>)
-            // Each possible branch only has a single hashmap operation,
-            // except for the replace case, which cannot be done with one:
-            // because we don't know whether to insert or replace until we've inserted
-            // at which point we'd need to either re-insert the old value,
-            // or somehow handle making the inserted value point to the right
-            // lens in the vector.
-            match action {
-                LensBoxAction::Insert(new_lens) => {
-                    match target_box.lens_map.get(&label) {
-                        Some(old_key) => old_key.borrow_mut().power = new_lens.power,
-                        None => {
-                            let lens = Rc::new(RefCell::new(new_lens));
-                            target_box
-                                .lens_map
-                                .insert(lens.borrow().name.clone(), lens.clone());
-                            target_box.ordered_lenses.push(lens);
-                        }
-                    };
-                }
-                LensBoxAction::Remove => {
-                    if let Some(old_lens) = target_box.lens_map.remove(&label) {
-                        old_lens.borrow_mut().power = None;
-                    }
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
+    }
+
+    fn part2_with_params(&self, mut input_lines: Lines, params: &Params) -> String {
+        let steps = input_lines.next().unwrap();
+        let mut library = LensLibrary::new();
+
+        if params.get_or("trace", false) {
+            let trace = library.trace(steps);
+            return format!("{}\n\n{trace}", library.focusing_power());
+        }
+
+        for step in steps.split(',') {
+            library.apply(Step::parse(step));
+        }
+        library.focusing_power().to_string()
+    }
+}
+
+fn hash(text: &str) -> usize {
+    text.chars()
+        .fold(0, |acc, c| ((acc + c as u8 as usize) * 17) % 256)
+}
+
+#[derive(Debug, PartialEq)]
+enum Step<'a> {
+    Insert { label: &'a str, focal_length: usize },
+    Remove { label: &'a str },
+}
+
+impl<'a> Step<'a> {
+    fn parse(text: &'a str) -> Step<'a> {
+        match text.split_once('=') {
+            Some((label, focal_length)) => Step::Insert {
+                label,
+                focal_length: focal_length.parse().unwrap(),
+            },
+            None => Step::Remove {
+                label: text.strip_suffix('-').unwrap(),
+            },
+        }
+    }
+}
+
+struct Lens<'a> {
+    label: &'a str,
+    focal_length: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct LensBox {
+    first: Option<usize>,
+    last: Option<usize>,
+}
+
+// Each box is a doubly linked list through `slots`, so a lens can be taken out of the middle of a
+// box without shuffling the rest.  Freed slots are reused.
+struct LensLibrary<'a> {
+    boxes: Vec<LensBox>,
+    slots: Vec<Lens<'a>>,
+    free_slots: Vec<usize>,
+    // A label always goes in the same box, so there's only ever one lens for each
+    slot_for_label: HashMap<&'a str, usize>,
+}
+
+impl<'a> LensLibrary<'a> {
+    fn new() -> LensLibrary<'a> {
+        LensLibrary {
+            boxes: vec![LensBox::default(); 256],
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            slot_for_label: HashMap::new(),
+        }
+    }
+
+    fn apply(&mut self, step: Step<'a>) {
+        match step {
+            Step::Insert {
+                label,
+                focal_length,
+            } => match self.slot_for_label.get(label) {
+                Some(slot) => self.slots[*slot].focal_length = focal_length,
+                None => self.push_lens(label, focal_length),
+            },
+            Step::Remove { label } => {
+                if let Some(slot) = self.slot_for_label.remove(label) {
+                    self.unlink(slot);
                 }
-            };
+            }
         }
+    }
+
+    fn push_lens(&mut self, label: &'a str, focal_length: usize) {
+        let lens_box = &mut self.boxes[hash(label)];
+        let lens = Lens {
+            label,
+            focal_length,
+            prev: lens_box.last,
+            next: None,
+        };
 
-        let focusing_power = lens_boxes
-            .iter()
-            .enumerate()
-            .map(|(box_ix, lens_box)| {
-                lens_box
-                    .ordered_lenses
-                    .iter()
-                    .filter(|lens| lens.borrow().power.is_some())
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot] = lens;
+                slot
+            }
+            None => {
+                self.slots.push(lens);
+                self.slots.len() - 1
+            }
+        };
+
+        match lens_box.last {
+            Some(last) => self.slots[last].next = Some(slot),
+            None => lens_box.first = Some(slot),
+        }
+        lens_box.last = Some(slot);
+        self.slot_for_label.insert(label, slot);
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let Lens {
+            label, prev, next, ..
+        } = self.slots[slot];
+        let lens_box = &mut self.boxes[hash(label)];
+
+        match prev {
+            Some(prev) => self.slots[prev].next = next,
+            None => lens_box.first = next,
+        }
+        match next {
+            Some(next) => self.slots[next].prev = prev,
+            None => lens_box.last = prev,
+        }
+        self.free_slots.push(slot);
+    }
+
+    fn lenses(&self, box_ix: usize) -> impl Iterator<Item = &Lens<'a>> {
+        let mut slot = self.boxes[box_ix].first;
+        std::iter::from_fn(move || {
+            let lens = &self.slots[slot?];
+            slot = lens.next;
+            Some(lens)
+        })
+    }
+
+    fn focusing_power(&self) -> usize {
+        (0..self.boxes.len())
+            .flat_map(|box_ix| {
+                self.lenses(box_ix)
                     .enumerate()
-                    .map(move |(lens_ix, lens)| {
-                        (1 + box_ix) * (1 + lens_ix) * lens.borrow().power.unwrap()
-                    })
+                    .map(move |(lens_ix, lens)| (1 + box_ix) * (1 + lens_ix) * lens.focal_length)
+            })
+            .sum()
+    }
+
+    fn trace(&mut self, steps: &'a str) -> String {
+        steps
+            .split(',')
+            .map(|step| {
+                self.apply(Step::parse(step));
+                format!("After \"{step}\":\n{self}")
             })
-            .flatten()
-            .sum::<usize>();
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
 
-        focusing_power.to_string()
+// The non-empty boxes, one to a line, like "Box 0: [rn 1] [cm 2]"
+impl Display for LensLibrary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for box_ix in 0..self.boxes.len() {
+            if self.boxes[box_ix].first.is_none() {
+                continue;
+            }
+            write!(f, "Box {box_ix}:")?;
+            for lens in self.lenses(box_ix) {
+                write!(f, " [{} {}]", lens.label, lens.focal_length)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -127,4 +221,78 @@ mod tests {
         let sample_input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
         assert_eq!(super::Solver15.part2(sample_input.lines()), "145");
     }
+
+    #[test]
+    fn trace() {
+        use super::LensLibrary;
+
+        let sample_input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
+        let expected = "After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After \"cm=2\":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+After \"qp-\":
+Box 0: [rn 1] [cm 2]
+
+After \"pc=4\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4]
+
+After \"ot=9\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9]
+
+After \"ab=5\":
+Box 0: [rn 1] [cm 2]
+Box 3: [pc 4] [ot 9] [ab 5]
+
+After \"pc-\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5]
+
+After \"pc=6\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 9] [ab 5] [pc 6]
+
+After \"ot=7\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+";
+        assert_eq!(LensLibrary::new().trace(sample_input), expected);
+
+        let mut params = crate::params::Params::new();
+        params.insert("trace=true");
+        assert_eq!(
+            super::Solver15.part2_with_params(sample_input.lines(), &params),
+            format!("145\n\n{expected}")
+        );
+    }
+
+    #[test]
+    fn removal_reuses_slots() {
+        use super::{LensLibrary, Step};
+
+        let mut library = LensLibrary::new();
+        for step in "rn=1,cm=2,qp=3,cm-,rn-,ab=4,rn=5".split(',') {
+            library.apply(Step::parse(step));
+        }
+
+        // Only as many slots as there were lenses at once
+        assert_eq!(library.slots.len(), 3);
+        assert_eq!(
+            library.to_string(),
+            "Box 0: [rn 5]\nBox 1: [qp 3]\nBox 3: [ab 4]\n"
+        );
+        assert_eq!(library.focusing_power(), 5 + 2 * 3 + 4 * 4);
+    }
 }
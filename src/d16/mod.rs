@@ -1,5 +1,7 @@
-use std::str::Lines;
+use std::{cmp::Reverse, collections::HashMap, str::Lines};
 
+use crate::graph::Graph;
+use crate::params::Params;
 use crate::Solver;
 pub struct Solver16;
 #[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
@@ -12,13 +14,60 @@ enum NodeType {
     BackSlash,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
 enum Direction {
     N = 0,
     E,
     S,
     W,
 }
+
+impl Direction {
+    // May be off the grid
+    fn step(self, x: isize, y: isize) -> (isize, isize) {
+        match self {
+            Direction::N => (x, y - 1),
+            Direction::E => (x + 1, y),
+            Direction::S => (x, y + 1),
+            Direction::W => (x - 1, y),
+        }
+    }
+}
+
+enum Exits {
+    One(Direction),
+    Split(Direction, Direction),
+}
+
+impl NodeType {
+    fn exits(self, dir: Direction) -> Exits {
+        match (dir, self) {
+            (Direction::W, NodeType::Empty)
+            | (Direction::W, NodeType::Horizontal)
+            | (Direction::N, NodeType::BackSlash)
+            | (Direction::S, NodeType::ForwardSlash) => Exits::One(Direction::W),
+            (Direction::E, NodeType::Empty)
+            | (Direction::E, NodeType::Horizontal)
+            | (Direction::N, NodeType::ForwardSlash)
+            | (Direction::S, NodeType::BackSlash) => Exits::One(Direction::E),
+            (Direction::N, NodeType::Empty)
+            | (Direction::N, NodeType::Vertical)
+            | (Direction::E, NodeType::ForwardSlash)
+            | (Direction::W, NodeType::BackSlash) => Exits::One(Direction::N),
+            (Direction::S, NodeType::Empty)
+            | (Direction::S, NodeType::Vertical)
+            | (Direction::E, NodeType::BackSlash)
+            | (Direction::W, NodeType::ForwardSlash) => Exits::One(Direction::S),
+            (Direction::N, NodeType::Horizontal) | (Direction::S, NodeType::Horizontal) => {
+                Exits::Split(Direction::W, Direction::E)
+            }
+            (Direction::E, NodeType::Vertical) | (Direction::W, NodeType::Vertical) => {
+                Exits::Split(Direction::N, Direction::S)
+            }
+        }
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
 
 struct Node {
@@ -58,6 +107,15 @@ impl Map {
         let max_y = grid.len() as isize - 1;
         Map { grid, max_x, max_y }
     }
+
+    fn parse(input_lines: Lines) -> Map {
+        Map::new(
+            input_lines
+                .map(|line| line.bytes().map(Node::new).collect())
+                .collect(),
+        )
+    }
+
     fn reset(&mut self) {
         self.grid.iter_mut().for_each(|row| {
             row.iter_mut().for_each(|node| {
@@ -73,84 +131,303 @@ impl Map {
             .get_mut::<usize>(x.try_into().ok()?)
     }
 
+    fn node_type(&self, x: isize, y: isize) -> Option<NodeType> {
+        let row = self.grid.get(usize::try_from(y).ok()?)?;
+        Some(row.get(usize::try_from(x).ok()?)?.node_type)
+    }
+
+    // In from the left and right of each row, then from the top and bottom of each column
+    fn edge_entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for y in 0..=self.max_y {
+            entries.push(Entry {
+                x: 0,
+                y,
+                dir: Direction::E,
+            });
+            entries.push(Entry {
+                x: self.max_x,
+                y,
+                dir: Direction::W,
+            });
+        }
+        for x in 0..=self.max_x {
+            entries.push(Entry {
+                x,
+                y: 0,
+                dir: Direction::S,
+            });
+            entries.push(Entry {
+                x,
+                y: self.max_y,
+                dir: Direction::N,
+            });
+        }
+        entries
+    }
+
+    // Beams still to follow go on a stack rather than recursing at each splitter, so that big
+    // contraptions can't overflow the call stack
     fn traverse(&mut self, x: isize, y: isize, dir: Direction) -> usize {
-        let mut current_x = x;
-        let mut current_y = y;
-        let mut current_dir = dir;
+        let mut beams = vec![(x, y, dir)];
         let mut lit = 0;
-        loop {
-            match self.get(current_y, current_x) {
-                None => return lit,
-                Some(current_node) => {
-                    // Has this node already been lit when it had the same output direction before?
-                    if current_node.entered_this_way_before[current_dir as usize] {
-                        return lit;
-                    }
-                    current_node.entered_this_way_before[current_dir as usize] = true;
 
-                    if !current_node.lit {
-                        lit += 1;
+        while let Some((mut current_x, mut current_y, mut current_dir)) = beams.pop() {
+            while let Some(current_node) = self.get(current_y, current_x) {
+                // Has this node already been lit when it had the same output direction before?
+                if current_node.entered_this_way_before[current_dir as usize] {
+                    break;
+                }
+                current_node.entered_this_way_before[current_dir as usize] = true;
+
+                if !current_node.lit {
+                    lit += 1;
+                }
+
+                current_node.lit = true;
+
+                match current_node.node_type.exits(current_dir) {
+                    Exits::One(dir) => {
+                        (current_x, current_y) = dir.step(current_x, current_y);
+                        current_dir = dir;
                     }
+                    Exits::Split(dir1, dir2) => {
+                        let (x2, y2) = dir2.step(current_x, current_y);
+                        beams.push((x2, y2, dir2));
+                        (current_x, current_y) = dir1.step(current_x, current_y);
+                        current_dir = dir1;
+                    }
+                }
+            }
+        }
 
-                    current_node.lit = true;
+        lit
+    }
+}
 
-                    match (current_dir, &current_node.node_type) {
-                        (Direction::W, NodeType::Empty)
-                        | (Direction::W, NodeType::Horizontal)
-                        | (Direction::N, NodeType::BackSlash)
-                        | (Direction::S, NodeType::ForwardSlash) => {
-                            current_x -= 1;
-                            current_dir = Direction::W;
-                        }
-                        (Direction::E, NodeType::Empty)
-                        | (Direction::E, NodeType::Horizontal)
-                        | (Direction::N, NodeType::ForwardSlash)
-                        | (Direction::S, NodeType::BackSlash) => {
-                            current_x += 1;
-                            current_dir = Direction::E;
-                        }
-                        (Direction::N, NodeType::Empty)
-                        | (Direction::N, NodeType::Vertical)
-                        | (Direction::E, NodeType::ForwardSlash)
-                        | (Direction::W, NodeType::BackSlash) => {
-                            current_y -= 1;
-                            current_dir = Direction::N;
-                        }
-                        (Direction::S, NodeType::Empty)
-                        | (Direction::S, NodeType::Vertical)
-                        | (Direction::E, NodeType::BackSlash)
-                        | (Direction::W, NodeType::ForwardSlash) => {
-                            current_y += 1;
-                            current_dir = Direction::S;
-                        }
-                        (Direction::N, NodeType::Horizontal)
-                        | (Direction::S, NodeType::Horizontal) => {
-                            return lit
-                                + self.traverse(current_x - 1, current_y, Direction::W)
-                                + self.traverse(current_x + 1, current_y, Direction::E);
-                        }
-                        (Direction::E, NodeType::Vertical) | (Direction::W, NodeType::Vertical) => {
-                            return lit
-                                + self.traverse(current_x, current_y - 1, Direction::N)
-                                + self.traverse(current_x, current_y + 1, Direction::S);
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+struct Entry {
+    x: isize,
+    y: isize,
+    dir: Direction,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum SweepMethod {
+    // Each thread has its own copy of the map
+    Parallel,
+    Segments,
+}
+
+// Ties go to the earliest entry
+fn best_entry(map: &Map, method: SweepMethod) -> (Entry, usize) {
+    let entries = map.edge_entries();
+    let energised = match method {
+        SweepMethod::Parallel => energised_in_parallel(map, &entries),
+        SweepMethod::Segments => {
+            let segments = BeamSegments::new(map);
+            entries.iter().map(|e| segments.energised(e)).collect()
+        }
+    };
+
+    let (ix, count) = energised
+        .into_iter()
+        .enumerate()
+        .max_by_key(|(ix, count)| (*count, Reverse(*ix)))
+        .unwrap();
+    (entries[ix], count)
+}
+
+fn energised_in_parallel(map: &Map, entries: &[Entry]) -> Vec<usize> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = entries.len().div_ceil(threads);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut map = map.clone();
+                    chunk
+                        .iter()
+                        .map(|entry| {
+                            map.reset();
+                            map.traverse(entry.x, entry.y, entry.dir)
+                        })
+                        .collect::<Vec<usize>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+struct Segment {
+    // Including the splitter at the end
+    tiles: Vec<usize>,
+    // Index in `BeamSegments::splitters`
+    end: Option<usize>,
+}
+
+// A beam reaching any splitter in a strongly connected component of the splitter graph energises
+// everything the component can reach, so that's worked out once for each component
+struct BeamSegments<'a> {
+    map: &'a Map,
+    width: usize,
+    splitter_at: HashMap<usize, usize>,
+    component_of: Vec<usize>,
+    energised: Vec<Vec<u64>>,
+}
+
+impl<'a> BeamSegments<'a> {
+    fn new(map: &'a Map) -> BeamSegments<'a> {
+        let width = map.grid[0].len();
+        let num_tiles = width * map.grid.len();
+
+        let mut splitters: Vec<(isize, isize)> = Vec::new();
+        for (y, row) in map.grid.iter().enumerate() {
+            for (x, node) in row.iter().enumerate() {
+                if matches!(node.node_type, NodeType::Horizontal | NodeType::Vertical) {
+                    splitters.push((x as isize, y as isize));
+                }
+            }
+        }
+        let splitter_at = splitters
+            .iter()
+            .enumerate()
+            .map(|(ix, (x, y))| (*y as usize * width + *x as usize, ix))
+            .collect();
+
+        let mut segments = BeamSegments {
+            map,
+            width,
+            splitter_at,
+            component_of: vec![0; splitters.len()],
+            energised: Vec::new(),
+        };
+
+        // The two segments out of each splitter
+        let outputs: Vec<[Segment; 2]> = splitters
+            .iter()
+            .map(|(x, y)| {
+                let node_type = map.node_type(*x, *y).unwrap();
+                let (dir1, dir2) = match node_type {
+                    NodeType::Horizontal => (Direction::W, Direction::E),
+                    _ => (Direction::N, Direction::S),
+                };
+                let (x1, y1) = dir1.step(*x, *y);
+                let (x2, y2) = dir2.step(*x, *y);
+                [segments.follow(x1, y1, dir1), segments.follow(x2, y2, dir2)]
+            })
+            .collect();
+
+        let mut graph = Graph::with_len(splitters.len());
+        for (ix, segments) in outputs.iter().enumerate() {
+            for segment in segments {
+                if let Some(end) = segment.end {
+                    graph.add_edge_ix(ix, end);
+                }
+            }
+        }
+
+        // Components come sinks first, so everything a component leads to is done before it
+        for (component_ix, component) in graph
+            .strongly_connected_components()
+            .into_iter()
+            .enumerate()
+        {
+            let mut energised = vec![0_u64; num_tiles.div_ceil(64)];
+            for splitter in &component {
+                segments.component_of[*splitter] = component_ix;
+            }
+            for splitter in &component {
+                let (x, y) = splitters[*splitter];
+                set_bit(&mut energised, y as usize * width + x as usize);
+
+                for segment in &outputs[*splitter] {
+                    for tile in &segment.tiles {
+                        set_bit(&mut energised, *tile);
+                    }
+                    if let Some(end) = segment.end {
+                        let end_component = segments.component_of[end];
+                        if end_component != component_ix {
+                            for (word, other) in
+                                energised.iter_mut().zip(&segments.energised[end_component])
+                            {
+                                *word |= other;
+                            }
                         }
                     }
                 }
             }
+            segments.energised.push(energised);
         }
+
+        segments
+    }
+
+    // Without splitting, the beam can only loop back to where it started
+    fn follow(&self, x: isize, y: isize, dir: Direction) -> Segment {
+        let (mut x, mut y, mut dir) = (x, y, dir);
+        let start = (x, y, dir);
+        let mut tiles = Vec::new();
+
+        while let Some(node_type) = self.map.node_type(x, y) {
+            let tile = y as usize * self.width + x as usize;
+            tiles.push(tile);
+
+            match node_type.exits(dir) {
+                Exits::One(next_dir) => {
+                    (x, y) = next_dir.step(x, y);
+                    dir = next_dir;
+                }
+                Exits::Split(..) => {
+                    return Segment {
+                        tiles,
+                        end: Some(self.splitter_at[&tile]),
+                    };
+                }
+            }
+
+            if (x, y, dir) == start {
+                break;
+            }
+        }
+
+        Segment { tiles, end: None }
+    }
+
+    fn energised(&self, entry: &Entry) -> usize {
+        let segment = self.follow(entry.x, entry.y, entry.dir);
+        let mut energised = match segment.end {
+            Some(end) => self.energised[self.component_of[end]].clone(),
+            None => vec![0; (self.width * self.map.grid.len()).div_ceil(64)],
+        };
+        for tile in segment.tiles {
+            set_bit(&mut energised, tile);
+        }
+        energised
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
     }
 }
+
+fn set_bit(bits: &mut [u64], ix: usize) {
+    bits[ix / 64] |= 1 << (ix % 64);
+}
+
 impl Solver for Solver16 {
     fn day_number(&self) -> u32 {
         16
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let grid: Vec<Vec<Node>> = input_lines
-            .map(|line| line.bytes().map(Node::new).collect())
-            .collect();
-
-        let mut map = Map::new(grid);
+        let mut map = Map::parse(input_lines);
 
         let lit = map.traverse(0, 0, Direction::E);
 
@@ -158,36 +435,26 @@ impl Solver for Solver16 {
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        let grid: Vec<Vec<Node>> = input_lines
-            .map(|line| line.bytes().map(Node::new).collect())
-            .collect();
-
-        let mut map = Map::new(grid);
-
-        let x_len = map.grid[0].len() as isize;
-        let y_len = map.grid.len() as isize;
-
-        let mut starts = Vec::with_capacity(2 * map.grid.len() + 2 * map.grid[0].len());
+        self.part2_with_params(input_lines, &Params::new())
+    }
 
-        for y in 0..y_len {
-            starts.push((0, y, Direction::E));
-            starts.push((x_len - 1, y, Direction::W));
-        }
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let map = Map::parse(input_lines);
+        let method = match params.get("sweep").unwrap_or("segments") {
+            "parallel" => SweepMethod::Parallel,
+            "segments" => SweepMethod::Segments,
+            other => panic!("Unknown sweep method {other}"),
+        };
 
-        for x in 0..x_len {
-            starts.push((x, 0, Direction::S));
-            starts.push((x, y_len - 1, Direction::N));
+        let (entry, energised) = best_entry(&map, method);
+        if params.get_or("show_entry", false) {
+            format!(
+                "{energised} from ({}, {}) heading {:?}",
+                entry.x, entry.y, entry.dir
+            )
+        } else {
+            energised.to_string()
         }
-
-        starts
-            .iter()
-            .map(|(x, y, dir)| {
-                map.reset();
-                map.traverse(*x, *y, *dir)
-            })
-            .max()
-            .unwrap()
-            .to_string()
     }
 }
 
@@ -223,6 +490,133 @@ mod tests {
 ..//.|....";
         assert_eq!(super::Solver16.part2(sample_input.lines()), "51");
     }
+    #[test]
+    fn sweep_methods_agree() {
+        use super::{best_entry, Direction, Entry, Map, SweepMethod};
+
+        let sample_input = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+        // Includes a beam which loops round through a splitter's pointy ends
+        let loop_input = r"/-\.
+|.|.
+\-/.
+....";
+
+        for input in [sample_input, loop_input] {
+            let map = Map::parse(input.lines());
+            let mut traversed = map.clone();
+            let segments = super::BeamSegments::new(&map);
+            for entry in map.edge_entries() {
+                traversed.reset();
+                assert_eq!(
+                    segments.energised(&entry),
+                    traversed.traverse(entry.x, entry.y, entry.dir),
+                    "{entry:?}"
+                );
+            }
+
+            assert_eq!(
+                best_entry(&map, SweepMethod::Parallel),
+                best_entry(&map, SweepMethod::Segments)
+            );
+        }
+
+        let map = Map::parse(sample_input.lines());
+        assert_eq!(
+            best_entry(&map, SweepMethod::Segments),
+            (
+                Entry {
+                    x: 3,
+                    y: 0,
+                    dir: Direction::S
+                },
+                51
+            )
+        );
+    }
+
+    #[test]
+    fn sweep_params() {
+        use crate::params::Params;
+
+        let sample_input = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
+        let mut params = Params::new();
+        params.insert("sweep=parallel");
+        params.insert("show_entry=true");
+        assert_eq!(
+            super::Solver16.part2_with_params(sample_input.lines(), &params),
+            "51 from (3, 0) heading S"
+        );
+    }
+
+    #[test]
+    fn dense_splitters() {
+        use super::{BeamSegments, Entry, Map};
+
+        // Mostly splitters, with a few mirrors and gaps, from a simple pseudo-random sequence
+        let grid = |size: usize| {
+            let mut state: u64 = 12345;
+            (0..size)
+                .map(|_| {
+                    (0..size)
+                        .map(|_| {
+                            state = state
+                                .wrapping_mul(6364136223846793005)
+                                .wrapping_add(1442695040888963407);
+                            match (state >> 33) % 10 {
+                                0..=3 => '|',
+                                4..=7 => '-',
+                                8 => '/',
+                                _ => '.',
+                            }
+                        })
+                        .collect::<String>()
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+
+        // Small enough to check against following the segments
+        let input = grid(40);
+        let map = Map::parse(input.lines());
+        let segments = BeamSegments::new(&map);
+        for entry in map.edge_entries() {
+            let mut traversed = map.clone();
+            assert_eq!(
+                traversed.traverse(entry.x, entry.y, entry.dir),
+                segments.energised(&entry)
+            );
+        }
+
+        // Big enough that recursing at every splitter would overflow the stack
+        let input = grid(1000);
+        let mut map = Map::parse(input.lines());
+        let entry = Entry {
+            x: 0,
+            y: 0,
+            dir: super::Direction::E,
+        };
+        let lit = map.traverse(entry.x, entry.y, entry.dir);
+        assert!(lit > 500_000 && lit <= 1_000_000, "{lit}");
+    }
+
     #[test]
     fn full_input_tests() {
         let input = include_str!("input.txt");
@@ -1,9 +1,10 @@
-use std::str::Lines;
+use std::{cmp::Reverse, collections::BinaryHeap, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 pub struct Solver17;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 enum Direction {
     N,
     E,
@@ -11,256 +12,238 @@ enum Direction {
     W,
 }
 
-#[derive(Clone, Debug)]
-struct Node {
-    x: isize,
-    y: isize,
-    heat_loss: usize,
-    leasts_by_dir: Vec<Vec<Option<usize>>>,
-}
+const DIRECTIONS: [Direction; 4] = [Direction::N, Direction::E, Direction::S, Direction::W];
 
-impl Node {
-    fn new(x: usize, y: usize, heat_loss: char, max_steps: usize) -> Self {
-        Self {
-            x: x as isize,
-            y: y as isize,
-            heat_loss: heat_loss.to_digit(10).unwrap() as usize,
-            leasts_by_dir: vec![vec![None; max_steps]; 4],
-        }
+impl Direction {
+    fn left(self) -> Direction {
+        DIRECTIONS[(self as usize + 3) % 4]
     }
 
-    fn make_start_node(&mut self) {
-        self.leasts_by_dir = vec![vec![Some(0); self.leasts_by_dir[0].len()]; 4];
+    fn right(self) -> Direction {
+        DIRECTIONS[(self as usize + 1) % 4]
     }
 
-    fn update_cost(
-        &mut self,
-        heat_loss: usize,
-        direction: Direction,
-        conseq_steps: usize,
-        search_nodes: &mut OrderedMap,
-    ) {
-        let leasts = &mut self.leasts_by_dir[direction as usize];
-        let heat_loss = heat_loss + self.heat_loss;
-
-        if leasts[conseq_steps].is_some_and(|loss| loss <= heat_loss) {
-            // We already have a better path
-            return;
-        }
-
-        leasts[conseq_steps] = Some(heat_loss);
-
-        search_nodes.add(heat_loss, (self.x, self.y, direction, conseq_steps));
+    fn reverse(self) -> Direction {
+        DIRECTIONS[(self as usize + 2) % 4]
     }
 }
 
-struct OrderedMap {
-    map: Vec<Vec<(isize, isize, Direction, usize)>>,
+#[derive(Clone, Debug)]
+struct CrucibleRules {
+    start: (usize, usize),
+    // Bottom right if not given
+    end: Option<(usize, usize)>,
+    // Before it can turn or stop
+    min_straight: usize,
+    max_straight: usize,
+    allow_left: bool,
+    allow_right: bool,
+    allow_reverse: bool,
+    // On top of the block's own heat loss
+    step_cost: usize,
+    turn_cost: usize,
 }
-impl OrderedMap {
-    fn new(s: usize) -> Self {
-        Self {
-            map: vec![vec![]; s],
+
+impl CrucibleRules {
+    fn new(min_straight: usize, max_straight: usize) -> CrucibleRules {
+        CrucibleRules {
+            start: (0, 0),
+            end: None,
+            min_straight,
+            max_straight,
+            allow_left: true,
+            allow_right: true,
+            allow_reverse: false,
+            step_cost: 0,
+            turn_cost: 0,
         }
     }
-    fn add(&mut self, key: usize, value: (isize, isize, Direction, usize)) {
-        self.map[key].push(value);
+
+    fn from_params(params: &Params, min_straight: usize, max_straight: usize) -> CrucibleRules {
+        let defaults = CrucibleRules::new(min_straight, max_straight);
+        CrucibleRules {
+            start: params.get("start").map_or(defaults.start, parse_cell),
+            end: params.get("end").map(parse_cell),
+            min_straight: params.get_or("min_straight", min_straight),
+            max_straight: params.get_or("max_straight", max_straight),
+            allow_left: !params.get_or("no_left", false),
+            allow_right: !params.get_or("no_right", false),
+            allow_reverse: params.get_or("reverse", defaults.allow_reverse),
+            step_cost: params.get_or("step_cost", defaults.step_cost),
+            turn_cost: params.get_or("turn_cost", defaults.turn_cost),
+        }
     }
 
-    fn pop(&mut self) -> Option<(isize, isize, Direction, usize)> {
-        self.map
-            .iter_mut()
-            .find(|vec| !vec.is_empty())
-            .and_then(|vec| vec.pop())
+    fn next_directions(&self, dir: Direction, run: usize) -> Vec<Direction> {
+        let mut directions = Vec::new();
+        if run < self.max_straight {
+            directions.push(dir);
+        }
+        if run >= self.min_straight {
+            if self.allow_left {
+                directions.push(dir.left());
+            }
+            if self.allow_right {
+                directions.push(dir.right());
+            }
+            if self.allow_reverse {
+                directions.push(dir.reverse());
+            }
+        }
+        directions
     }
 }
-struct Map {
-    grid: Grid,
-    search_nodes: OrderedMap,
-    min_straight_line: usize,
-    max_straight_line: usize,
+
+fn parse_cell(text: &str) -> (usize, usize) {
+    let (x, y) = text.split_once(',').unwrap();
+    (x.parse().unwrap(), y.parse().unwrap())
 }
 
-struct Grid {
-    inner: Vec<Vec<Node>>,
+struct City {
+    heat_loss: Vec<Vec<usize>>,
 }
 
-impl Grid {
-    fn get_mut(&mut self, y: isize, x: isize) -> Option<&mut Node> {
-        self.inner
-            .get_mut::<usize>(y.try_into().ok()?)?
-            .get_mut::<usize>(x.try_into().ok()?)
+impl City {
+    fn parse(input_lines: Lines) -> City {
+        City {
+            heat_loss: input_lines
+                .map(|line| {
+                    line.chars()
+                        .map(|c| c.to_digit(10).unwrap() as usize)
+                        .collect()
+                })
+                .collect(),
+        }
     }
-}
 
-impl Map {
-    #[allow(dead_code)]
-    fn print(&self) {
-        for row in &self.grid.inner {
-            println!("Y: {} ", row[0].y);
-            for node in row {
-                println!(
-                    "X: {} {:?} ",
-                    node.x,
-                    node.leasts_by_dir
-                        .iter()
-                        .map(|v| v.iter().map(|v| v.unwrap_or(0)).collect::<Vec<usize>>())
-                        .collect::<Vec<Vec<usize>>>()
-                );
-            }
-            println!();
+    fn width(&self) -> usize {
+        self.heat_loss[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.heat_loss.len()
+    }
+
+    fn step(&self, (x, y): (usize, usize), dir: Direction) -> Option<(usize, usize)> {
+        match dir {
+            Direction::N => Some((x, y.checked_sub(1)?)),
+            Direction::E => Some((x + 1, y)).filter(|(x, _)| *x < self.width()),
+            Direction::S => Some((x, y + 1)).filter(|(_, y)| *y < self.height()),
+            Direction::W => Some((x.checked_sub(1)?, y)),
         }
     }
-    fn solve_from_location(&mut self, x: isize, y: isize, end_location: (usize, usize)) -> usize {
-        self.search_nodes.add(0, (x, y, Direction::N, 0));
-        self.search_nodes.add(0, (x, y, Direction::E, 0));
-
-        self.grid.get_mut(y, x).unwrap().make_start_node();
-
-        while let Some((x, y, dir, steps)) = self.search_nodes.pop() {
-            let current_node = &self.grid.inner[y as usize][x as usize];
-            if (x as usize, y as usize) == end_location {
-                if let Some(val) = current_node
-                    .leasts_by_dir
-                    .iter()
-                    .flat_map(|v| v[self.min_straight_line - 1..].iter().filter_map(|v| *v))
-                    .min()
-                {
-                    return val;
+
+    // Dijkstra's algorithm over states of (block, direction, blocks moved in a straight line).  The
+    // start has its own state with no direction, from which the crucible can go any way.
+    fn least_heat_loss(&self, rules: &CrucibleRules) -> Option<(usize, Vec<(usize, usize)>)> {
+        let end = rules.end.unwrap_or((self.width() - 1, self.height() - 1));
+        let runs = rules.max_straight + 1;
+
+        // Directions 0-3 are N, E, S, W, and 4 is the start state's lack of direction
+        let state_ix = |(x, y): (usize, usize), dir: usize, run: usize| {
+            ((y * self.width() + x) * 5 + dir) * runs + run
+        };
+        let state = |ix: usize| {
+            let (cell, dir, run) = (ix / runs / 5, ix / runs % 5, ix % runs);
+            ((cell % self.width(), cell / self.width()), dir, run)
+        };
+
+        let num_states = self.width() * self.height() * 5 * runs;
+        let mut best = vec![usize::MAX; num_states];
+        let mut previous = vec![usize::MAX; num_states];
+        let mut queue = BinaryHeap::new();
+
+        let start = state_ix(rules.start, 4, 0);
+        best[start] = 0;
+        queue.push(Reverse((0, start)));
+
+        while let Some(Reverse((heat_loss, ix))) = queue.pop() {
+            if heat_loss > best[ix] {
+                continue;
+            }
+
+            let (cell, dir, run) = state(ix);
+            if cell == end && (dir == 4 || run >= rules.min_straight) {
+                let mut path = vec![cell];
+                let mut ix = ix;
+                while previous[ix] != usize::MAX {
+                    ix = previous[ix];
+                    path.push(state(ix).0);
                 }
+                path.reverse();
+                return Some((heat_loss, path));
             }
 
-            // Take of copy of the current node to use for values when updating surrounding nodes
-            // Resolves ownership / multiple borrow issues
-            // Update each of the reachable nodes
-            let heat_loss =
-                self.grid.inner[y as usize][x as usize].leasts_by_dir[dir as usize][steps].unwrap();
-
-            match dir {
-                Direction::N | Direction::S => {
-                    let mut updated_heat_loss = heat_loss;
-                    for step_ix in 0..std::cmp::min(
-                        self.max_straight_line,
-                        self.grid.inner[0].len() - x as usize - 1,
-                    ) {
-                        let node =
-                            &mut self.grid.inner[y as usize][(x + step_ix as isize + 1) as usize];
-                        if step_ix >= self.min_straight_line - 1 {
-                            node.update_cost(
-                                updated_heat_loss,
-                                Direction::E,
-                                step_ix,
-                                &mut self.search_nodes,
-                            );
-                        }
-                        updated_heat_loss += node.heat_loss;
-                    }
-                    let mut updated_heat_loss = heat_loss;
-                    for step_ix in 0..std::cmp::min(self.max_straight_line, x as usize) {
-                        let node =
-                            &mut self.grid.inner[y as usize][(x - step_ix as isize - 1) as usize];
-
-                        // if let Some(node) = self.grid.get_mut(y, x - step_ix as isize - 1) {
-                        if step_ix >= self.min_straight_line - 1 {
-                            node.update_cost(
-                                updated_heat_loss,
-                                Direction::W,
-                                step_ix,
-                                &mut self.search_nodes,
-                            );
-                        }
-                        updated_heat_loss += node.heat_loss;
-                    }
+            let next_directions = match dir {
+                4 => DIRECTIONS.to_vec(),
+                _ => rules.next_directions(DIRECTIONS[dir], run),
+            };
+
+            for next_dir in next_directions {
+                let Some(next_cell) = self.step(cell, next_dir) else {
+                    continue;
+                };
+                let turned = dir != 4 && DIRECTIONS[dir] != next_dir;
+                let next_run = if dir == 4 || turned { 1 } else { run + 1 };
+                if next_run > rules.max_straight {
+                    continue;
                 }
 
-                Direction::W | Direction::E => {
-                    let mut updated_heat_loss = heat_loss;
-                    for step_ix in 0..std::cmp::min(
-                        self.max_straight_line,
-                        self.grid.inner.len() - y as usize - 1,
-                    ) {
-                        let node =
-                            &mut self.grid.inner[(y + step_ix as isize + 1) as usize][x as usize];
-                        if step_ix >= self.min_straight_line - 1 {
-                            node.update_cost(
-                                updated_heat_loss,
-                                Direction::S,
-                                step_ix,
-                                &mut self.search_nodes,
-                            );
-                        }
-                        updated_heat_loss += node.heat_loss;
-                    }
-                    let mut updated_heat_loss = heat_loss;
-                    for step_ix in 0..std::cmp::min(self.max_straight_line, y as usize) {
-                        let node =
-                            &mut self.grid.inner[(y - step_ix as isize - 1) as usize][x as usize];
-
-                        if step_ix >= self.min_straight_line - 1 {
-                            node.update_cost(
-                                updated_heat_loss,
-                                Direction::N,
-                                step_ix,
-                                &mut self.search_nodes,
-                            );
-                        }
-                        updated_heat_loss += node.heat_loss;
-                    }
+                let next_heat_loss = heat_loss
+                    + self.heat_loss[next_cell.1][next_cell.0]
+                    + rules.step_cost
+                    + if turned { rules.turn_cost } else { 0 };
+                let next_ix = state_ix(next_cell, next_dir as usize, next_run);
+                if next_heat_loss < best[next_ix] {
+                    best[next_ix] = next_heat_loss;
+                    previous[next_ix] = ix;
+                    queue.push(Reverse((next_heat_loss, next_ix)));
                 }
             }
         }
-        panic!("No path found");
+
+        None
     }
 }
+
 impl Solver for Solver17 {
     fn day_number(&self) -> u32 {
         17
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let inner_grid = input_lines
-            .enumerate()
-            .map(|(line_ix, line)| {
-                line.char_indices()
-                    .map(|(c_ix, c)| Node::new(c_ix, line_ix, c, 3))
-                    .collect::<Vec<Node>>()
-            })
-            .collect::<Vec<Vec<Node>>>();
-
-        let end_location = (inner_grid[0].len() - 1, inner_grid.len() - 1);
-
-        let mut map = Map {
-            grid: Grid { inner: inner_grid },
-            search_nodes: OrderedMap::new((end_location.0 + 1) * (end_location.1 + 1) * 10),
-            min_straight_line: 1,
-            max_straight_line: 3,
-        };
-
-        map.solve_from_location(0, 0, end_location).to_string()
+        self.part1_with_params(input_lines, &Params::new())
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        let inner_grid = input_lines
-            .enumerate()
-            .map(|(line_ix, line)| {
-                line.char_indices()
-                    .map(|(c_ix, c)| Node::new(c_ix, line_ix, c, 10))
-                    .collect::<Vec<Node>>()
-            })
-            .collect::<Vec<Vec<Node>>>();
-
-        let end_location = (inner_grid[0].len() - 1, inner_grid.len() - 1);
-
-        let min_straight_line = 4;
-        let max_straight_line = 10;
-
-        let mut map = Map {
-            grid: Grid { inner: inner_grid },
-            search_nodes: OrderedMap::new((end_location.0 + 1) * (end_location.1 + 1) * 10),
-            min_straight_line,
-            max_straight_line,
-        };
-        map.solve_from_location(0, 0, end_location).to_string()
+        self.part2_with_params(input_lines, &Params::new())
+    }
+
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve(
+            input_lines,
+            params,
+            CrucibleRules::from_params(params, 1, 3),
+        )
+    }
+
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        solve(
+            input_lines,
+            params,
+            CrucibleRules::from_params(params, 4, 10),
+        )
+    }
+}
+
+fn solve(input_lines: Lines, params: &Params, rules: CrucibleRules) -> String {
+    match City::parse(input_lines).least_heat_loss(&rules) {
+        None => "no path".to_string(),
+        Some((heat_loss, path)) if params.get_or("show_path", false) => {
+            let path: Vec<String> = path.iter().map(|(x, y)| format!("({x},{y})")).collect();
+            format!("{heat_loss} via {}", path.join(" "))
+        }
+        Some((heat_loss, _)) => heat_loss.to_string(),
     }
 }
 
@@ -310,6 +293,117 @@ mod tests {
         assert_eq!(super::Solver17.part2(sample_input_2.lines()), "71");
     }
 
+    #[test]
+    fn paths() {
+        use super::{City, CrucibleRules};
+
+        let sample_input = "2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+        let city = City::parse(sample_input.lines());
+
+        for rules in [CrucibleRules::new(1, 3), CrucibleRules::new(4, 10)] {
+            let (heat_loss, path) = city.least_heat_loss(&rules).unwrap();
+            assert_eq!(path.first(), Some(&(0, 0)));
+            assert_eq!(path.last(), Some(&(12, 12)));
+
+            // One block at a time, adding up to the heat loss
+            assert!(path
+                .windows(2)
+                .all(|w| w[0].0.abs_diff(w[1].0) + w[0].1.abs_diff(w[1].1) == 1));
+            let total: usize = path[1..].iter().map(|(x, y)| city.heat_loss[*y][*x]).sum();
+            assert_eq!(total, heat_loss);
+
+            // Never further than the limit in a straight line
+            let in_a_row = path
+                .windows(rules.max_straight + 2)
+                .filter(|w| w.iter().all(|c| c.0 == w[0].0) || w.iter().all(|c| c.1 == w[0].1))
+                .count();
+            assert_eq!(in_a_row, 0);
+        }
+    }
+
+    #[test]
+    fn turn_restrictions() {
+        use crate::params::Params;
+
+        // Going south then east is a left turn, and east then south a right turn
+        let sample_input = "19
+11";
+        let mut params = Params::new();
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "2"
+        );
+        params.insert("no_left=true");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "10"
+        );
+        params.insert("no_right=true");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "no path"
+        );
+
+        let mut params = Params::new();
+        params.insert("turn_cost=5");
+        params.insert("show_path=true");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "7 via (0,0) (0,1) (1,1)"
+        );
+    }
+
+    #[test]
+    fn reversing() {
+        use crate::params::Params;
+
+        // Moving two blocks at a time, the crucible can only stop one block along by going past
+        // and coming back
+        let sample_input = "11111";
+        let mut params = Params::new();
+        params.insert("end=1,0");
+        params.insert("min_straight=2");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "no path"
+        );
+        params.insert("reverse=true");
+        params.insert("show_path=true");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "5 via (0,0) (1,0) (2,0) (3,0) (2,0) (1,0)"
+        );
+    }
+
+    #[test]
+    fn start_end_and_step_costs() {
+        use crate::params::Params;
+
+        let sample_input = "1234
+5678";
+        let mut params = Params::new();
+        params.insert("start=3,1");
+        params.insert("end=0,0");
+        params.insert("step_cost=10");
+        params.insert("show_path=true");
+        assert_eq!(
+            super::Solver17.part1_with_params(sample_input.lines(), &params),
+            "50 via (3,1) (3,0) (2,0) (1,0) (0,0)"
+        );
+    }
+
     #[test]
     fn test_actual() {
         let input = include_str!("input.txt");
@@ -1,158 +1,138 @@
-use std::{collections::VecDeque, str::Lines, vec};
+use std::{fmt::Display, str::Lines};
 
+use crate::geometry::{Point, Polygon};
 use crate::Solver;
 pub struct Solver18;
 
-#[derive(Debug, Copy, Clone)]
-struct Point {
-    x: isize,
-    y: isize,
-}
-#[derive(Debug, Clone, Copy)]
-struct Line {
-    top: Point,
-    bottom: Point,
-}
-
-impl Line {
-    fn truncate(&mut self, new_bottom: isize, lines: &mut VecDeque<Line>) {
-        let mut remainder = *self;
-        remainder.top.y = new_bottom;
-        insert(lines, remainder);
-        self.bottom.y = new_bottom;
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Rectangle {
-    top_left: Point,
-    bottom_right: Point,
-}
-
 impl Solver for Solver18 {
     fn day_number(&self) -> u32 {
         18
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let distance_and_directions = input_lines.map(|line| {
-            let mut instruction = line.split(' ');
-            let dir = instruction.next().unwrap().as_bytes()[0];
-            let dist = instruction.next().unwrap().parse::<isize>().unwrap();
-            (dist, dir)
-        });
-
-        find_area(distance_and_directions)
+        find_area(input_lines.map(part1_instruction))
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        let distance_and_directions = input_lines.map(|line| {
-            let instruction = line.split('#').nth(1).unwrap();
-            let (dist, dir) = instruction.split_at(5);
-            let dist = isize::from_str_radix(dist, 16).unwrap();
-            let dir = match dir.as_bytes()[0] {
-                b'0' => b'R',
-                b'1' => b'D',
-                b'2' => b'L',
-                b'3' => b'U',
-                _ => panic!("Unknown direction"),
-            };
-            (dist, dir)
-        });
-
-        find_area(distance_and_directions)
+        find_area(input_lines.map(part2_instruction))
     }
 }
 
-fn find_area(distance_and_directions: impl Iterator<Item = (isize, u8)>) -> String {
-    let mut current_loc = Point { x: 0, y: 0 };
-    let mut vert_lines: VecDeque<Line> = vec![].into();
-    let mut total_line_length = 0_i64;
-
-    for (dist, dir) in distance_and_directions {
-        total_line_length += dist as i64;
-        match dir {
-            b'R' => current_loc.x += dist,
-            b'L' => current_loc.x -= dist,
-            b'U' => {
-                let bottom = current_loc;
-                current_loc.y -= dist;
-                let top = current_loc;
-                vert_lines.push_back(Line { top, bottom });
-            }
-            b'D' => {
-                let top = current_loc;
-                current_loc.y += dist;
-                let bottom = current_loc;
-                vert_lines.push_back(Line { top, bottom });
-            }
-            _ => panic!("Unknown direction"),
-        }
-    }
-
-    vert_lines
-        .make_contiguous()
-        .sort_by(|a, b| a.top.y.cmp(&b.top.y).then(a.top.x.cmp(&b.top.x)));
+fn part1_instruction(line: &str) -> (isize, u8) {
+    let mut instruction = line.split(' ');
+    let dir = instruction.next().unwrap().as_bytes()[0];
+    let dist = instruction.next().unwrap().parse::<isize>().unwrap();
+    (dist, dir)
+}
 
-    let mut sum = 0_i64;
+// e.g. "(#70c710)": five hex digits of distance, then the direction
+fn part2_instruction(line: &str) -> (isize, u8) {
+    let colour = line
+        .rsplit(' ')
+        .next()
+        .and_then(|c| c.strip_prefix("(#"))
+        .and_then(|c| c.strip_suffix(')'))
+        .filter(|c| c.len() == 6)
+        .unwrap_or_else(|| panic!("No colour in {line}"));
+    let (dist, dir) = colour.split_at(5);
+    let dist = isize::from_str_radix(dist, 16).unwrap();
+    let dir = match dir {
+        "0" => b'R',
+        "1" => b'D',
+        "2" => b'L',
+        "3" => b'U',
+        _ => panic!("Unknown direction"),
+    };
+    (dist, dir)
+}
 
-    while let Some(mut opening_line) = vert_lines.pop_front() {
-        let closing_line_ix = vert_lines
-            .iter()
-            .position(|line| line.top.y == opening_line.top.y)
-            .unwrap();
-        let mut closing_line = vert_lines.remove(closing_line_ix).unwrap();
+#[derive(Debug, PartialEq)]
+enum DigPlanError {
+    Unclosed(Point),
+    // At these instructions, counting from 1
+    SelfIntersecting(usize, usize),
+    // At this instruction, counting from 1
+    NegativeDistance(usize),
+    NoMoves,
+    TooBig,
+}
 
-        match opening_line.bottom.y.cmp(&closing_line.bottom.y) {
-            std::cmp::Ordering::Less => {
-                closing_line.truncate(opening_line.bottom.y, &mut vert_lines);
+impl Display for DigPlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DigPlanError::Unclosed(end) => write!(
+                f,
+                "trench isn't closed: it ends at ({}, {}) rather than (0, 0)",
+                end.x, end.y
+            ),
+            DigPlanError::SelfIntersecting(a, b) => {
+                write!(f, "trench crosses itself at instructions {a} and {b}")
             }
-            std::cmp::Ordering::Greater => {
-                opening_line.truncate(closing_line.bottom.y, &mut vert_lines);
+            DigPlanError::NegativeDistance(ix) => {
+                write!(f, "instruction {ix} has a negative distance")
             }
-            std::cmp::Ordering::Equal => {}
+            DigPlanError::NoMoves => write!(f, "dig plan doesn't go anywhere"),
+            DigPlanError::TooBig => write!(f, "trench is too long"),
         }
+    }
+}
 
-        let common_rect = Rectangle {
-            top_left: opening_line.top,
-            bottom_right: closing_line.bottom,
-        };
+fn dig_plan(
+    distance_and_directions: impl Iterator<Item = (isize, u8)>,
+) -> Result<Polygon, DigPlanError> {
+    let start = Point { x: 0, y: 0 };
+    let mut current_loc = start;
+    let mut vertices = vec![current_loc];
+    // The instruction which dug each edge, as zero-length moves don't make one
+    let mut instructions = vec![];
 
-        let mut interfering_line_ix = None;
-        for (ix, line) in vert_lines.iter_mut().enumerate() {
-            if line.top.y < common_rect.bottom_right.y
-                && line.top.x > common_rect.top_left.x
-                && line.top.x < common_rect.bottom_right.x
-            {
-                interfering_line_ix = Some(ix);
-                break;
-            }
+    for (ix, (dist, dir)) in distance_and_directions.enumerate() {
+        if dist < 0 {
+            return Err(DigPlanError::NegativeDistance(ix + 1));
         }
-
-        if let Some(interfering_line_ix) = interfering_line_ix {
-            opening_line.truncate(vert_lines[interfering_line_ix].top.y, &mut vert_lines);
-            closing_line.truncate(vert_lines[interfering_line_ix].top.y, &mut vert_lines);
+        if dist == 0 {
+            continue;
         }
+        let dist = dist as i64;
+        let (x, y) = match dir {
+            b'R' => (current_loc.x.checked_add(dist), Some(current_loc.y)),
+            b'L' => (current_loc.x.checked_sub(dist), Some(current_loc.y)),
+            b'U' => (Some(current_loc.x), current_loc.y.checked_sub(dist)),
+            b'D' => (Some(current_loc.x), current_loc.y.checked_add(dist)),
+            _ => panic!("Unknown direction"),
+        };
+        current_loc = Point {
+            x: x.ok_or(DigPlanError::TooBig)?,
+            y: y.ok_or(DigPlanError::TooBig)?,
+        };
+        vertices.push(current_loc);
+        instructions.push(ix + 1);
+    }
 
-        sum += (closing_line.bottom.y - opening_line.top.y) as i64
-            * (closing_line.bottom.x - opening_line.top.x) as i64;
+    if current_loc != start {
+        return Err(DigPlanError::Unclosed(current_loc));
+    }
+    if instructions.is_empty() {
+        return Err(DigPlanError::NoMoves);
     }
 
-    sum += 1 + (total_line_length / 2 as i64);
-    sum.to_string()
+    let polygon = Polygon::new(vertices);
+    match polygon.self_intersection() {
+        Some((a, b)) => Err(DigPlanError::SelfIntersecting(
+            instructions[a],
+            instructions[b],
+        )),
+        None => Ok(polygon),
+    }
 }
 
-fn insert(vert_lines: &mut VecDeque<Line>, line_to_insert: Line) {
-    for ii in 0..vert_lines.len() {
-        let line = vert_lines[ii];
-        if line.top.y > line_to_insert.top.y
-            || (line.top.y == line_to_insert.top.y && line.top.x > line_to_insert.top.x)
-        {
-            vert_lines.insert(ii, line_to_insert);
-            return;
-        }
+fn find_area(distance_and_directions: impl Iterator<Item = (isize, u8)>) -> String {
+    // The trench is one cube wide, centred on the dig path, so the lagoon covers every lattice
+    // point inside or on the path.
+    match dig_plan(distance_and_directions) {
+        Ok(polygon) => polygon.area_including_boundary().to_string(),
+        Err(error) => error.to_string(),
     }
-    vert_lines.push_back(line_to_insert);
 }
 
 #[cfg(test)]
@@ -195,4 +175,275 @@ L 2 (#015232)
 U 2 (#7a21e3)";
         assert_eq!(super::Solver18.part2(sample_input.lines()), "952408144115");
     }
+
+    #[test]
+    fn sweep_matches_polygon_area() {
+        let sample_input = "R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+        for parse in [super::part1_instruction, super::part2_instruction] {
+            assert_eq!(
+                sweep::find_area_by_sweep(sample_input.lines().map(parse)),
+                super::find_area(sample_input.lines().map(parse))
+            );
+        }
+
+        // A plus shape, which has vertical edges starting part way down the sweep
+        let plus = [
+            (2, b'R'),
+            (2, b'U'),
+            (2, b'R'),
+            (2, b'D'),
+            (2, b'R'),
+            (2, b'D'),
+            (2, b'L'),
+            (2, b'D'),
+            (2, b'L'),
+            (2, b'U'),
+            (2, b'L'),
+            (2, b'U'),
+        ];
+        assert_eq!(sweep::find_area_by_sweep(plus.into_iter()), "33");
+        assert_eq!(super::find_area(plus.into_iter()), "33");
+    }
+
+    #[test]
+    fn invalid_plans() {
+        use super::{dig_plan, DigPlanError};
+        use crate::geometry::Point;
+        use crate::Solver;
+
+        assert_eq!(
+            dig_plan([(2, b'R'), (2, b'D'), (1, b'L')].into_iter()).unwrap_err(),
+            DigPlanError::Unclosed(Point { x: 1, y: 2 })
+        );
+
+        // A figure of eight, crossing in the middle
+        let figure_of_eight = [
+            (2, b'R'),
+            (4, b'D'),
+            (2, b'R'),
+            (2, b'U'),
+            (4, b'L'),
+            (2, b'U'),
+        ];
+        assert_eq!(
+            dig_plan(figure_of_eight.into_iter()).unwrap_err(),
+            DigPlanError::SelfIntersecting(2, 5)
+        );
+        assert_eq!(
+            super::find_area(figure_of_eight.into_iter()),
+            "trench crosses itself at instructions 2 and 5"
+        );
+
+        // Going back along the trench just dug
+        let doubling_back = [(3, b'R'), (1, b'L'), (2, b'D'), (2, b'L'), (2, b'U')];
+        assert_eq!(
+            dig_plan(doubling_back.into_iter()).unwrap_err(),
+            DigPlanError::SelfIntersecting(1, 2)
+        );
+
+        // A zero-length move is fine, and doesn't throw out the instruction numbers
+        let standing_still = [(2, b'R'), (0, b'R'), (2, b'D'), (2, b'L'), (2, b'U')];
+        assert_eq!(super::find_area(standing_still.into_iter()), "9");
+        let doubling_back = [
+            (3, b'R'),
+            (0, b'D'),
+            (1, b'L'),
+            (2, b'D'),
+            (2, b'L'),
+            (2, b'U'),
+        ];
+        assert_eq!(
+            dig_plan(doubling_back.into_iter()).unwrap_err(),
+            DigPlanError::SelfIntersecting(1, 3)
+        );
+
+        // Nowhere to fill, rather than a lagoon of one cube
+        assert_eq!(dig_plan([].into_iter()).unwrap_err(), DigPlanError::NoMoves);
+        assert_eq!(
+            dig_plan([(0, b'R'), (0, b'D')].into_iter()).unwrap_err(),
+            DigPlanError::NoMoves
+        );
+
+        // Rather than silently digging the other way
+        assert_eq!(
+            dig_plan([(2, b'R'), (-2, b'R')].into_iter()).unwrap_err(),
+            DigPlanError::NegativeDistance(2)
+        );
+        assert_eq!(
+            super::Solver18.part1("R 2 (#000020)\nR -2 (#000022)".lines()),
+            "instruction 2 has a negative distance"
+        );
+
+        assert_eq!(
+            dig_plan([(isize::MAX, b'R'), (isize::MAX, b'R')].into_iter()).unwrap_err(),
+            DigPlanError::TooBig
+        );
+    }
+
+    #[test]
+    fn huge_plan() {
+        // The sample scaled up so that the area doesn't fit in an i64
+        let sample_input = "R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+        let scale = 1_000_000_000;
+        let scaled = sample_input
+            .lines()
+            .map(super::part1_instruction)
+            .map(|(dist, dir)| (dist * scale, dir));
+        // The sample's path encloses 42 square metres and is 38 metres long, and the lagoon
+        // covers the area plus half the trench's length plus 1
+        let scale = scale as i128;
+        let expected = 42 * scale * scale + 19 * scale + 1;
+        assert_eq!(super::find_area(scaled), expected.to_string());
+        assert!(expected > i64::MAX as i128);
+    }
+
+    // The original rectangle-sweep area calculation, kept to cross-check the polygon maths.
+    mod sweep {
+        use std::collections::VecDeque;
+
+        #[derive(Debug, Copy, Clone)]
+        struct Point {
+            x: isize,
+            y: isize,
+        }
+        #[derive(Debug, Clone, Copy)]
+        struct Line {
+            top: Point,
+            bottom: Point,
+        }
+
+        impl Line {
+            fn truncate(&mut self, new_bottom: isize, lines: &mut VecDeque<Line>) {
+                let mut remainder = *self;
+                remainder.top.y = new_bottom;
+                insert(lines, remainder);
+                self.bottom.y = new_bottom;
+            }
+        }
+
+        #[derive(Debug, Clone, Copy)]
+        struct Rectangle {
+            top_left: Point,
+            bottom_right: Point,
+        }
+
+        pub(super) fn find_area_by_sweep(
+            distance_and_directions: impl Iterator<Item = (isize, u8)>,
+        ) -> String {
+            let mut current_loc = Point { x: 0, y: 0 };
+            let mut vert_lines: VecDeque<Line> = vec![].into();
+            let mut total_line_length = 0_i64;
+
+            for (dist, dir) in distance_and_directions {
+                total_line_length += dist as i64;
+                match dir {
+                    b'R' => current_loc.x += dist,
+                    b'L' => current_loc.x -= dist,
+                    b'U' => {
+                        let bottom = current_loc;
+                        current_loc.y -= dist;
+                        let top = current_loc;
+                        vert_lines.push_back(Line { top, bottom });
+                    }
+                    b'D' => {
+                        let top = current_loc;
+                        current_loc.y += dist;
+                        let bottom = current_loc;
+                        vert_lines.push_back(Line { top, bottom });
+                    }
+                    _ => panic!("Unknown direction"),
+                }
+            }
+
+            vert_lines
+                .make_contiguous()
+                .sort_by(|a, b| a.top.y.cmp(&b.top.y).then(a.top.x.cmp(&b.top.x)));
+
+            let mut sum = 0_i64;
+
+            while let Some(mut opening_line) = vert_lines.pop_front() {
+                let closing_line_ix = vert_lines
+                    .iter()
+                    .position(|line| line.top.y == opening_line.top.y)
+                    .unwrap();
+                let mut closing_line = vert_lines.remove(closing_line_ix).unwrap();
+
+                match opening_line.bottom.y.cmp(&closing_line.bottom.y) {
+                    std::cmp::Ordering::Less => {
+                        closing_line.truncate(opening_line.bottom.y, &mut vert_lines);
+                    }
+                    std::cmp::Ordering::Greater => {
+                        opening_line.truncate(closing_line.bottom.y, &mut vert_lines);
+                    }
+                    std::cmp::Ordering::Equal => {}
+                }
+
+                let common_rect = Rectangle {
+                    top_left: opening_line.top,
+                    bottom_right: closing_line.bottom,
+                };
+
+                let mut interfering_line_ix = None;
+                for (ix, line) in vert_lines.iter_mut().enumerate() {
+                    if line.top.y < common_rect.bottom_right.y
+                        && line.top.x > common_rect.top_left.x
+                        && line.top.x < common_rect.bottom_right.x
+                    {
+                        interfering_line_ix = Some(ix);
+                        break;
+                    }
+                }
+
+                if let Some(interfering_line_ix) = interfering_line_ix {
+                    opening_line.truncate(vert_lines[interfering_line_ix].top.y, &mut vert_lines);
+                    closing_line.truncate(vert_lines[interfering_line_ix].top.y, &mut vert_lines);
+                }
+
+                sum += (closing_line.bottom.y - opening_line.top.y) as i64
+                    * (closing_line.bottom.x - opening_line.top.x) as i64;
+            }
+
+            sum += 1 + (total_line_length / 2);
+            sum.to_string()
+        }
+
+        fn insert(vert_lines: &mut VecDeque<Line>, line_to_insert: Line) {
+            for ii in 0..vert_lines.len() {
+                let line = vert_lines[ii];
+                if line.top.y > line_to_insert.top.y
+                    || (line.top.y == line_to_insert.top.y && line.top.x > line_to_insert.top.x)
+                {
+                    vert_lines.insert(ii, line_to_insert);
+                    return;
+                }
+            }
+            vert_lines.push_back(line_to_insert);
+        }
+    }
 }
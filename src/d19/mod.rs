@@ -1,27 +1,183 @@
-use core::panic;
-use std::{collections::HashMap, str::Lines};
+use std::{collections::HashMap, fmt, str::Lines};
 
 use itertools::Itertools;
 
+use crate::graph::Graph;
+use crate::params::Params;
+use crate::parse::{braced, key_values, next_section};
 use crate::Solver;
 pub struct Solver19;
 
-#[derive(Debug, Clone)]
-enum RuleResult {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    X,
+    M,
+    A,
+    S,
+}
+
+const CATEGORIES: [Category; 4] = [Category::X, Category::M, Category::A, Category::S];
+
+impl Category {
+    fn parse(text: &str) -> Category {
+        match text {
+            "x" => Category::X,
+            "m" => Category::M,
+            "a" => Category::A,
+            "s" => Category::S,
+            _ => panic!("Unknown category {text}"),
+        }
+    }
+
+    fn name(self) -> char {
+        match self {
+            Category::X => 'x',
+            Category::M => 'm',
+            Category::A => 'a',
+            Category::S => 's',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    Greater,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Condition {
+    category: Category,
+    comparison: Comparison,
+    value: i64,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Condition {
+        let (category, comparison, value) = if let Some((category, value)) = text.split_once('<') {
+            (category, Comparison::Less, value)
+        } else if let Some((category, value)) = text.split_once('>') {
+            (category, Comparison::Greater, value)
+        } else {
+            panic!("Unknown condition {text}")
+        };
+
+        Condition {
+            category: Category::parse(category),
+            comparison,
+            value: value.parse().unwrap(),
+        }
+    }
+
+    fn matches(&self, ratings: &[i64; 4]) -> bool {
+        let rating = ratings[self.category as usize];
+        match self.comparison {
+            Comparison::Less => rating < self.value,
+            Comparison::Greater => rating > self.value,
+        }
+    }
+
+    // (matching, not matching)
+    fn split(&self, part: Part) -> (Option<Part>, Option<Part>) {
+        let range = part.ranges[self.category as usize];
+        let (low, high) = match self.comparison {
+            Comparison::Less => (self.value - 1, self.value),
+            Comparison::Greater => (self.value, self.value + 1),
+        };
+        let below = part.with(self.category, range.min, range.max.min(low));
+        let above = part.with(self.category, range.min.max(high), range.max);
+
+        match self.comparison {
+            Comparison::Less => (below, above),
+            Comparison::Greater => (above, below),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let comparison = match self.comparison {
+            Comparison::Less => '<',
+            Comparison::Greater => '>',
+        };
+        write!(f, "{}{comparison}{}", self.category.name(), self.value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target<'a> {
     Accept,
     Reject,
-    Pass,
-    NewRule(String),
+    Workflow(&'a str),
 }
-#[derive(Debug, Clone, Copy)]
-struct Part {
-    x: Range,
-    m: Range,
-    a: Range,
-    s: Range,
+
+impl<'a> Target<'a> {
+    fn parse(text: &'a str) -> Target<'a> {
+        match text {
+            "A" => Target::Accept,
+            "R" => Target::Reject,
+            _ => Target::Workflow(text),
+        }
+    }
+}
+
+impl fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Accept => write!(f, "A"),
+            Target::Reject => write!(f, "R"),
+            Target::Workflow(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule<'a> {
+    condition: Option<Condition>,
+    target: Target<'a>,
+}
+
+impl<'a> Rule<'a> {
+    fn parse(text: &'a str) -> Rule<'a> {
+        match text.split_once(':') {
+            Some((condition, target)) => Rule {
+                condition: Some(Condition::parse(condition)),
+                target: Target::parse(target),
+            },
+            None => Rule {
+                condition: None,
+                target: Target::parse(text),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Rule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.condition {
+            Some(condition) => write!(f, "{condition}:{}", self.target),
+            None => write!(f, "{}", self.target),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Workflow<'a> {
+    name: &'a str,
+    rules: Vec<Rule<'a>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl<'a> Workflow<'a> {
+    fn parse(line: &'a str) -> Workflow<'a> {
+        let (name, rules) = braced(line);
+        Workflow {
+            name,
+            rules: rules.split(',').map(Rule::parse).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Range {
     min: i64,
     max: i64,
@@ -33,137 +189,433 @@ impl Range {
     }
 }
 
-fn attr_x(val: &mut Part) -> &mut Range {
-    &mut val.x
+// A box of parts, with an inclusive range of ratings in each category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Part {
+    ranges: [Range; 4],
 }
 
-fn attr_m(val: &mut Part) -> &mut Range {
-    &mut val.m
+impl Part {
+    fn all() -> Part {
+        Part {
+            ranges: [Range { min: 1, max: 4000 }; 4],
+        }
+    }
+
+    // `None` if the new range is empty
+    fn with(&self, category: Category, min: i64, max: i64) -> Option<Part> {
+        (min <= max).then(|| {
+            let mut part = *self;
+            part.ranges[category as usize] = Range { min, max };
+            part
+        })
+    }
+
+    fn combinations(&self) -> i64 {
+        self.ranges.iter().map(Range::diff).product()
+    }
 }
 
-fn attr_a(val: &mut Part) -> &mut Range {
-    &mut val.a
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges = CATEGORIES
+            .iter()
+            .zip(self.ranges)
+            .map(|(category, range)| format!("{}={}..={}", category.name(), range.min, range.max));
+        write!(f, "{}", ranges.format(" "))
+    }
 }
 
-fn attr_s(val: &mut Part) -> &mut Range {
-    &mut val.s
+// Only for parts with ratings 1..=4000, as branches no such part can take are left out
+#[derive(Debug)]
+enum Decision {
+    Accept,
+    Reject,
+    Branch {
+        condition: Condition,
+        then: Box<Decision>,
+        otherwise: Box<Decision>,
+    },
 }
 
-fn build_compare_func(rule: Vec<char>) -> Box<dyn Fn(Part) -> Vec<(Part, RuleResult)>> {
-    let rulestring = rule[2..].iter().collect::<String>();
-    let (cmp_val, dest) = rulestring.split_once(':').unwrap();
-    let dest = dest.to_string();
-    let cmp_val = cmp_val.parse::<i64>().unwrap();
-
-    let attribute = match rule[0] {
-        'x' => Box::new(&attr_x) as Box<dyn Fn(&mut Part) -> &mut Range>,
-        'm' => Box::new(&attr_m) as Box<dyn Fn(&mut Part) -> &mut Range>,
-        'a' => Box::new(&attr_a) as Box<dyn Fn(&mut Part) -> &mut Range>,
-        's' => Box::new(&attr_s) as Box<dyn Fn(&mut Part) -> &mut Range>,
-        _ => {
-            panic!("Unknown attribute {}", rule[0])
-        }
-    };
-
-    let action = match { dest.as_str() } {
-        "R" => RuleResult::Reject,
-        "A" => RuleResult::Accept,
-        _ => RuleResult::NewRule(dest.clone()),
-    };
-
-    let (split_offset, lower_action, higher_action) = match rule[1] {
-        '<' => (0, action, RuleResult::Pass),
-        '>' => (1, RuleResult::Pass, action),
-        _ => {
-            panic!("Unknown comparitor {}", rule[1])
+impl Decision {
+    fn accepts(&self, ratings: &[i64; 4]) -> bool {
+        let mut decision = self;
+        loop {
+            match decision {
+                Decision::Accept => return true,
+                Decision::Reject => return false,
+                Decision::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    decision = if condition.matches(ratings) {
+                        then
+                    } else {
+                        otherwise
+                    }
+                }
+            }
         }
-    };
-
-    Box::new(move |mut range| {
-        if attribute(&mut range).max < cmp_val + split_offset {
-            // All values are lower
-            vec![(range, lower_action.clone())]
-        } else if attribute(&mut range).min > cmp_val + split_offset - 1 {
-            // All values are higher
-            vec![(range, higher_action.clone())]
-        } else {
-            // split the range
-            let mut low_range = range;
-            let mut high_range = range;
-            attribute(&mut low_range).max = cmp_val + split_offset - 1;
-            attribute(&mut high_range).min = cmp_val + split_offset;
-            vec![
-                (low_range, lower_action.clone()),
-                (high_range, higher_action.clone()),
-            ]
+    }
+
+    fn accepted_parts(&self, part: Part) -> Vec<Part> {
+        let mut accepted = vec![];
+        let mut pending = vec![(self, part)];
+
+        while let Some((decision, part)) = pending.pop() {
+            match decision {
+                Decision::Accept => accepted.push(part),
+                Decision::Reject => (),
+                Decision::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let (matching, rest) = condition.split(part);
+                    // Push the otherwise branch first so boxes come out in rule order
+                    pending.extend(rest.map(|part| (otherwise.as_ref(), part)));
+                    pending.extend(matching.map(|part| (then.as_ref(), part)));
+                }
+            }
         }
-    })
+
+        accepted
+    }
 }
 
-fn build_constant_func(res: RuleResult) -> Box<dyn Fn(Part) -> Vec<(Part, RuleResult)>> {
-    Box::new(move |p| vec![(p, res.clone())])
+// Rules and workflows are indexed the same as in the `System`
+#[derive(Debug)]
+struct Analysis {
+    reached: Vec<bool>,
+    // Combinations taking each rule, and how many of those are eventually accepted
+    matched: Vec<Vec<i64>>,
+    accepted_through: Vec<Vec<i64>>,
+    // Combinations which arrive back at a workflow they've already been through
+    looping: i64,
+    accepted: Vec<Part>,
 }
 
-fn apply_rules(
-    rules: HashMap<String, Vec<Box<dyn Fn(Part) -> Vec<(Part, RuleResult)>>>>,
-    mut parts: Vec<(Part, String)>,
-) -> Vec<Part> {
-    let mut accepted_parts = vec![];
-
-    while let Some((mut part_for_workflow, workflow_name)) = parts.pop() {
-        let workflow = rules.get(&workflow_name).unwrap();
-        for rule in workflow {
-            let rule_output = rule(part_for_workflow);
-            if let Some(part_for_next_workflow) = rule_output
-                .into_iter()
-                .filter_map(|(p, action)| match action {
-                    RuleResult::Pass => Some(p),
-                    RuleResult::Accept => {
-                        accepted_parts.push(p);
-                        None
+struct System<'a> {
+    workflows: Vec<Workflow<'a>>,
+    indices: HashMap<&'a str, usize>,
+}
+
+impl<'a> System<'a> {
+    fn parse(input_lines: &mut Lines<'a>) -> System<'a> {
+        let workflows = next_section(input_lines).map(Workflow::parse).collect_vec();
+        let indices: HashMap<_, _> = workflows
+            .iter()
+            .enumerate()
+            .map(|(ix, workflow)| (workflow.name, ix))
+            .collect();
+
+        assert!(indices.contains_key("in"), "No `in` workflow");
+        for workflow in &workflows {
+            for rule in &workflow.rules {
+                if let Target::Workflow(name) = rule.target {
+                    assert!(
+                        indices.contains_key(name),
+                        "Workflow {} sends parts to unknown workflow {name}",
+                        workflow.name
+                    );
+                }
+            }
+        }
+
+        System { workflows, indices }
+    }
+
+    fn start(&self) -> usize {
+        self.indices["in"]
+    }
+
+    fn graph(&self) -> Graph<'a> {
+        let mut graph = Graph::new();
+        // Add every node first so the graph indices match the workflow indices
+        for workflow in &self.workflows {
+            graph.node(workflow.name);
+        }
+        for workflow in &self.workflows {
+            for rule in &workflow.rules {
+                if let Target::Workflow(name) = rule.target {
+                    graph.add_edge(workflow.name, name);
+                }
+            }
+        }
+        graph
+    }
+
+    fn cycles(&self) -> Vec<Vec<usize>> {
+        let graph = self.graph();
+        graph
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || graph.neighbours(component[0]).contains(&component[0])
+            })
+            .collect()
+    }
+
+    // Gives the workflows round the loop if any parts can go round one for ever
+    fn compile(&self) -> Result<Box<Decision>, Vec<usize>> {
+        self.compile_rules(self.start(), 0, Part::all(), &mut vec![self.start()])
+    }
+
+    // Branches which no part in the box can take are left out, so a cycle only matters if some
+    // parts can follow it
+    fn compile_rules(
+        &self,
+        workflow: usize,
+        first_rule: usize,
+        part: Part,
+        path: &mut Vec<usize>,
+    ) -> Result<Box<Decision>, Vec<usize>> {
+        let rule = &self.workflows[workflow].rules[first_rule];
+        let (matching, rest) = match rule.condition {
+            None => (Some(part), None),
+            Some(condition) => condition.split(part),
+        };
+
+        let then = match matching {
+            Some(matching) => Some(self.compile_target(rule.target, matching, path)?),
+            None => None,
+        };
+        let otherwise = match rest {
+            Some(rest) => Some(self.compile_rules(workflow, first_rule + 1, rest, path)?),
+            None => None,
+        };
+
+        Ok(match (then, otherwise) {
+            (Some(decision), None) | (None, Some(decision)) => decision,
+            (Some(then), Some(otherwise)) => match (then.as_ref(), otherwise.as_ref()) {
+                // Both ways give the same answer, so there's no need to ask
+                (Decision::Accept, Decision::Accept) | (Decision::Reject, Decision::Reject) => then,
+                _ => Box::new(Decision::Branch {
+                    condition: rule.condition.unwrap(),
+                    then,
+                    otherwise,
+                }),
+            },
+            (None, None) => unreachable!("Every part either matches the rule or doesn't"),
+        })
+    }
+
+    fn compile_target(
+        &self,
+        target: Target,
+        part: Part,
+        path: &mut Vec<usize>,
+    ) -> Result<Box<Decision>, Vec<usize>> {
+        match target {
+            Target::Accept => Ok(Box::new(Decision::Accept)),
+            Target::Reject => Ok(Box::new(Decision::Reject)),
+            Target::Workflow(name) => {
+                let next = self.indices[name];
+                if let Some(pos) = path.iter().position(|&workflow| workflow == next) {
+                    return Err(path[pos..].to_vec());
+                }
+
+                path.push(next);
+                let decision = self.compile_rules(next, 0, part, path);
+                path.pop();
+                decision
+            }
+        }
+    }
+
+    fn analyze(&self) -> Analysis {
+        let mut analysis = Analysis {
+            reached: vec![false; self.workflows.len()],
+            matched: self
+                .workflows
+                .iter()
+                .map(|workflow| vec![0; workflow.rules.len()])
+                .collect(),
+            accepted_through: self
+                .workflows
+                .iter()
+                .map(|workflow| vec![0; workflow.rules.len()])
+                .collect(),
+            looping: 0,
+            accepted: vec![],
+        };
+
+        let mut on_path = vec![false; self.workflows.len()];
+        on_path[self.start()] = true;
+        self.propagate(self.start(), Part::all(), &mut on_path, &mut analysis);
+        analysis
+    }
+
+    // Returns how many combinations are accepted
+    fn propagate(
+        &self,
+        workflow: usize,
+        mut part: Part,
+        on_path: &mut Vec<bool>,
+        analysis: &mut Analysis,
+    ) -> i64 {
+        analysis.reached[workflow] = true;
+        let mut total_accepted = 0;
+
+        for (rule_ix, rule) in self.workflows[workflow].rules.iter().enumerate() {
+            let (matching, rest) = match rule.condition {
+                None => (Some(part), None),
+                Some(condition) => condition.split(part),
+            };
+
+            if let Some(matching) = matching {
+                let combinations = matching.combinations();
+                let accepted = match rule.target {
+                    Target::Accept => {
+                        analysis.accepted.push(matching);
+                        combinations
                     }
-                    RuleResult::NewRule(r) => {
-                        parts.push((p, r));
-                        None
+                    Target::Reject => 0,
+                    Target::Workflow(name) => {
+                        let next = self.indices[name];
+                        if on_path[next] {
+                            analysis.looping += combinations;
+                            0
+                        } else {
+                            on_path[next] = true;
+                            let accepted = self.propagate(next, matching, on_path, analysis);
+                            on_path[next] = false;
+                            accepted
+                        }
                     }
-                    RuleResult::Reject => None,
-                })
-                .collect::<Vec<Part>>()
-                .get(0)
-            {
-                part_for_workflow = *part_for_next_workflow;
-            } else {
-                break;
+                };
+
+                analysis.matched[workflow][rule_ix] += combinations;
+                analysis.accepted_through[workflow][rule_ix] += accepted;
+                total_accepted += accepted;
+            }
+
+            match rest {
+                Some(rest) => part = rest,
+                None => break,
             }
         }
+
+        total_accepted
     }
 
-    accepted_parts
-}
+    fn report(&self) -> String {
+        let analysis = self.analyze();
+        let names = |ixs: &[usize]| ixs.iter().map(|&ix| self.workflows[ix].name).join(", ");
 
-fn build_rules(
-    input_lines: &mut Lines,
-) -> HashMap<String, Vec<Box<dyn Fn(Part) -> Vec<(Part, RuleResult)>>>> {
-    let mut rules = HashMap::new();
-    while let Some((key, workflow)) = input_lines.next().and_then(|l| l.split_once('{')) {
-        let mut workflow_vec = vec![];
-
-        for rule in workflow.trim_end_matches('}').split(',') {
-            let rule: Vec<char> = rule.chars().collect();
-            let rule = match rule[0] {
-                'R' => build_constant_func(RuleResult::Reject),
-                'A' => build_constant_func(RuleResult::Accept),
-                _ => match rule[1] {
-                    '>' | '<' => build_compare_func(rule),
-                    _ => build_constant_func(RuleResult::NewRule(rule.iter().collect::<String>())),
-                },
-            };
-            workflow_vec.push(rule);
+        let unreachable = (0..self.workflows.len())
+            .filter(|&ix| !analysis.reached[ix])
+            .map(|ix| self.workflows[ix].name.to_string())
+            .collect_vec();
+        let matched = &analysis.matched;
+        let dead_rules = self
+            .workflows
+            .iter()
+            .enumerate()
+            .filter(|&(ix, _)| analysis.reached[ix])
+            .flat_map(|(ix, workflow)| {
+                workflow
+                    .rules
+                    .iter()
+                    .enumerate()
+                    .filter(move |&(rule_ix, _)| matched[ix][rule_ix] == 0)
+                    .map(move |(rule_ix, rule)| {
+                        format!("{}#{} ({rule})", workflow.name, rule_ix + 1)
+                    })
+            })
+            .collect_vec();
+        let cycles = self
+            .cycles()
+            .iter()
+            .map(|cycle| format!("[{}]", names(cycle)))
+            .collect_vec();
+
+        let or_none = |items: Vec<String>| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+
+        let mut report = vec![
+            format!(
+                "{} workflows, {} rules",
+                self.workflows.len(),
+                self.workflows.iter().map(|w| w.rules.len()).sum::<usize>()
+            ),
+            format!("unreachable workflows: {}", or_none(unreachable)),
+            format!("dead rules: {}", or_none(dead_rules)),
+            format!("cycles: {}", or_none(cycles)),
+        ];
+        if analysis.looping > 0 {
+            report.push(format!("looping combinations: {}", analysis.looping));
         }
+        report.push(format!(
+            "accepted regions: {}, {} combinations",
+            analysis.accepted.len(),
+            analysis
+                .accepted
+                .iter()
+                .map(Part::combinations)
+                .sum::<i64>()
+        ));
+        report.extend(analysis.accepted.iter().map(|part| format!("  {part}")));
+        report.join("\n")
+    }
 
-        rules.insert(key.to_string(), workflow_vec);
+    // With `flow`, edges also show how many accepted combinations go along them
+    fn to_dot(&self, flow: bool) -> String {
+        let analysis = flow.then(|| self.analyze());
+        let mut dot = vec![
+            "digraph workflows {".to_string(),
+            "  \"A\" [shape=box, color=green];".to_string(),
+            "  \"R\" [shape=box, color=red];".to_string(),
+        ];
+
+        for (ix, workflow) in self.workflows.iter().enumerate() {
+            for (rule_ix, rule) in workflow.rules.iter().enumerate() {
+                let mut label = rule
+                    .condition
+                    .map(|c| c.to_string())
+                    .into_iter()
+                    .collect_vec();
+                if let Some(analysis) = &analysis {
+                    label.push(analysis.accepted_through[ix][rule_ix].to_string());
+                }
+                let attributes = if label.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [label=\"{}\"]", label.join("\\n"))
+                };
+                dot.push(format!(
+                    "  \"{}\" -> \"{}\"{attributes};",
+                    workflow.name, rule.target
+                ));
+            }
+        }
+
+        dot.push("}".to_string());
+        dot.join("\n")
     }
-    rules
+}
+
+fn parse_ratings(line: &str) -> [i64; 4] {
+    let (_, ratings) = braced(line);
+    let mut parsed = [0; 4];
+    for (category, value) in key_values(ratings) {
+        parsed[Category::parse(category) as usize] = value.parse().unwrap();
+    }
+    parsed
+}
+
+fn describe_cycle(system: &System, cycle: &[usize]) -> String {
+    format!(
+        "workflows loop: {}",
+        cycle.iter().map(|&ix| system.workflows[ix].name).join(", ")
+    )
 }
 
 impl Solver for Solver19 {
@@ -172,65 +624,49 @@ impl Solver for Solver19 {
     }
 
     fn part1(&self, mut input_lines: Lines) -> String {
-        let rules = build_rules(&mut input_lines);
-
-        let parts = input_lines
-            .map(|line| {
-                let attributes = line.split(',').map(|part| {
-                    let (_, val) = part
-                        .trim_matches(|c| c == '{' || c == '}')
-                        .split_once('=')
-                        .unwrap();
-                    val.parse::<i64>().unwrap()
-                });
-                let (x, m, a, s) = attributes.collect_tuple().unwrap();
-                let part = Part {
-                    x: Range { min: x, max: x },
-                    m: Range { min: m, max: m },
-                    a: Range { min: a, max: a },
-                    s: Range { min: s, max: s },
-                };
-
-                (part, "in".to_string())
-            })
-            .collect_vec();
-
-        apply_rules(rules, parts)
-            .iter()
-            .map(|p| [p.x.max, p.m.max, p.a.max, p.s.max])
+        let system = System::parse(&mut input_lines);
+        let decision = match system.compile() {
+            Ok(decision) => decision,
+            Err(cycle) => return describe_cycle(&system, &cycle),
+        };
+
+        input_lines
+            .map(parse_ratings)
+            .filter(|ratings| decision.accepts(ratings))
             .flatten()
             .sum::<i64>()
             .to_string()
     }
 
     fn part2(&self, mut input_lines: Lines) -> String {
-        let rules = build_rules(&mut input_lines);
-
-        let parts = vec![(
-            Part {
-                x: Range { min: 1, max: 4000 },
-                m: Range { min: 1, max: 4000 },
-                a: Range { min: 1, max: 4000 },
-                s: Range { min: 1, max: 4000 },
-            },
-            "in".to_string(),
-        )];
-
-        apply_rules(rules, parts)
+        let system = System::parse(&mut input_lines);
+        let decision = match system.compile() {
+            Ok(decision) => decision,
+            Err(cycle) => return describe_cycle(&system, &cycle),
+        };
+
+        decision
+            .accepted_parts(Part::all())
             .iter()
-            .map(|p| {
-                [p.x, p.m, p.a, p.s]
-                    .iter()
-                    .map(|r| r.diff())
-                    .product::<i64>()
-            })
+            .map(Part::combinations)
             .sum::<i64>()
             .to_string()
     }
+
+    fn analyze(&self, mut input_lines: Lines, params: &Params) -> String {
+        let system = System::parse(&mut input_lines);
+        if params.get_or("dot", false) {
+            system.to_dot(params.get_or("flow", false))
+        } else {
+            system.report()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Part, System, Workflow};
+    use crate::params::Params;
     use crate::Solver;
     #[test]
     fn part1() {
@@ -278,4 +714,168 @@ hdj{m>838:A,pv}
             "167409079868000"
         );
     }
+
+    const SAMPLE: &str = "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
+
+    #[test]
+    fn workflow_round_trip() {
+        for line in SAMPLE.lines().take_while(|line| !line.is_empty()) {
+            let workflow = Workflow::parse(line);
+            let rules = workflow.rules.iter().map(|rule| rule.to_string());
+            let rules: Vec<_> = rules.collect();
+            assert_eq!(format!("{}{{{}}}", workflow.name, rules.join(",")), line);
+        }
+    }
+
+    #[test]
+    fn decision_tree_matches_analysis() {
+        let system = System::parse(&mut SAMPLE.lines());
+        let decision = system.compile().unwrap();
+        let from_tree = decision.accepted_parts(Part::all());
+        let from_analysis = system.analyze().accepted;
+
+        // The tree skips tests whose branches agree (all of `qs` is accepted), so its boxes can
+        // be bigger, but they have to cover the same parts
+        let total = |parts: &[Part]| parts.iter().map(Part::combinations).sum::<i64>();
+        assert_eq!(total(&from_tree), total(&from_analysis));
+        assert!(from_tree.len() < from_analysis.len());
+
+        let contains = |parts: &[Part], ratings: [i64; 4]| {
+            parts.iter().any(|part| {
+                part.ranges
+                    .iter()
+                    .zip(ratings)
+                    .all(|(range, rating)| range.min <= rating && rating <= range.max)
+            })
+        };
+        for line in SAMPLE.lines().skip_while(|line| !line.is_empty()).skip(1) {
+            let ratings = super::parse_ratings(line);
+            let in_box = contains(&from_tree, ratings);
+            assert_eq!(contains(&from_analysis, ratings), in_box);
+            assert_eq!(decision.accepts(&ratings), in_box);
+        }
+    }
+
+    #[test]
+    fn analysis() {
+        let report = System::parse(&mut SAMPLE.lines()).report();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(
+            lines[..5],
+            [
+                "11 workflows, 25 rules",
+                "unreachable workflows: none",
+                "dead rules: none",
+                "cycles: none",
+                "accepted regions: 9, 167409079868000 combinations",
+            ]
+        );
+        assert_eq!(lines.len(), 5 + 9);
+
+        let input = "in{x<100:a,x>50:b,R}
+a{x>200:c,A}
+b{m<4001:A,R}
+c{A}";
+        let report = System::parse(&mut input.lines()).report();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(
+            lines[..5],
+            [
+                "4 workflows, 8 rules",
+                "unreachable workflows: c",
+                "dead rules: in#3 (R), a#1 (x>200:c), b#2 (R)",
+                "cycles: none",
+                "accepted regions: 2, 256000000000000 combinations",
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let input = "in{x<100:loop,A}
+loop{m<10:in,R}
+
+{x=1,m=1,a=1,s=1}";
+        assert_eq!(
+            super::Solver19.part1(input.lines()),
+            "workflows loop: in, loop"
+        );
+        assert!(super::Solver19
+            .part2(input.lines())
+            .starts_with("workflows loop: "));
+
+        let report = System::parse(&mut input.lines()).report();
+        assert!(report.contains("cycles: [in, loop]") || report.contains("cycles: [loop, in]"));
+        assert!(report.contains(&format!(
+            "looping combinations: {}",
+            99 * 9 * 4000 * 4000_i64
+        )));
+        assert!(report.contains(&format!(
+            "accepted regions: 1, {} combinations",
+            3901 * 4000 * 4000 * 4000_i64
+        )));
+    }
+
+    #[test]
+    fn dot_export() {
+        let mut params = Params::new();
+        params.insert("dot=true");
+        let dot = super::Solver19.analyze(SAMPLE.lines(), &params);
+        let lines: Vec<_> = dot.lines().collect();
+        assert_eq!(lines[0], "digraph workflows {");
+        assert_eq!(lines.last(), Some(&"}"));
+        assert!(lines.contains(&"  \"px\" -> \"qkq\" [label=\"a<2006\"];"));
+        assert!(lines.contains(&"  \"in\" -> \"qqz\";"));
+        // Header, two terminal nodes and an edge per rule
+        assert_eq!(lines.len(), 3 + 25 + 1);
+
+        params.insert("flow=true");
+        let dot = super::Solver19.analyze(SAMPLE.lines(), &params);
+        let flow_from_in: i64 = dot
+            .lines()
+            .filter(|line| line.starts_with("  \"in\" -> "))
+            .map(|line| {
+                let (_, label) = line.split_once("label=\"").unwrap();
+                let label = label.strip_suffix("\"];").unwrap();
+                label.rsplit("\\n").next().unwrap().parse::<i64>().unwrap()
+            })
+            .sum();
+        assert_eq!(flow_from_in, 167409079868000);
+        assert!(dot.contains("  \"gd\" -> \"R\" [label=\"a>3333\\n0\"];"));
+    }
+
+    #[test]
+    fn cycle_no_part_can_follow() {
+        // Parts can only go back to `in` with x<100 and x>200
+        let input = "in{x<100:a,A}
+a{x>200:in,R}
+
+{x=1,m=1,a=1,s=1}
+{x=300,m=1,a=1,s=1}";
+        assert_eq!(super::Solver19.part1(input.lines()), "303");
+        assert_eq!(
+            super::Solver19.part2(input.lines()),
+            (3901 * 4000 * 4000 * 4000_i64).to_string()
+        );
+
+        let report = System::parse(&mut input.lines()).report();
+        assert!(report.contains("cycles: [a, in]") || report.contains("cycles: [in, a]"));
+        assert!(!report.contains("looping combinations"));
+    }
 }
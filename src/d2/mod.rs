@@ -1,65 +1,132 @@
-use std::{cmp::max, str::Lines};
+use std::{cmp::max, collections::HashMap, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 
 pub struct Solver2;
 
+// As it would be given with `--param bag=...`
+const DEFAULT_BAG: &str = "red:12,green:13,blue:14";
+
 impl Solver for Solver2 {
     fn day_number(&self) -> u32 {
         2
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let mut valid_game_sum = 0;
-        let matcher = regex::Regex::new(r"(\d+) (green|blue|red)").unwrap();
-
-        for line in input_lines {
-            let parts: Vec<&str> = line.split(":").collect();
-            let colors = matcher.captures_iter(parts[1]);
-            let mut maxes = [0, 0, 0];
-
-            for c in colors {
-                let count = c[1].parse::<u32>().unwrap();
-                match c[2].as_ref() {
-                    "red" => maxes[0] = max(count, maxes[0]),
-                    "green" => maxes[1] = max(count, maxes[1]),
-                    "blue" => maxes[2] = max(count, maxes[2]),
-                    _ => panic!("Unknown color"),
-                }
-            }
+        self.part1_with_params(input_lines, &Params::new())
+    }
 
-            if maxes[0] <= 12 && maxes[1] <= 13 && maxes[2] <= 14 {
-                valid_game_sum += parts[0][5..].parse::<u32>().unwrap();
-            }
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
+    }
+
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let bag = Cubes::parse_bag(params.get("bag").unwrap_or(DEFAULT_BAG));
+        let games: Vec<Game> = input_lines.map(Game::parse).collect();
+
+        possible_games(&games, &bag)
+            .map(|game| game.id)
+            .sum::<u32>()
+            .to_string()
+    }
+
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        // Only the colours in the bag count towards the power
+        let bag = Cubes::parse_bag(params.get("bag").unwrap_or(DEFAULT_BAG));
+        let colours: Vec<&str> = bag.counts.keys().copied().collect();
+
+        input_lines
+            .map(|line| Game::parse(line).minimal_bag().power(&colours))
+            .sum::<u32>()
+            .to_string()
+    }
+}
+
+// Either one handful drawn from the bag, or the bag itself
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Cubes<'a> {
+    counts: HashMap<&'a str, u32>,
+}
+
+impl<'a> Cubes<'a> {
+    // e.g. "3 blue, 4 red"
+    fn parse_draw(text: &'a str) -> Cubes<'a> {
+        let counts = text
+            .split(',')
+            .map(|cubes| {
+                let (count, colour) = cubes.trim().split_once(' ').unwrap();
+                (colour, count.parse::<u32>().unwrap())
+            })
+            .collect();
+        Cubes { counts }
+    }
+
+    // e.g. "red:12,green:13"
+    fn parse_bag(text: &'a str) -> Cubes<'a> {
+        let counts = text
+            .split(',')
+            .map(|cubes| {
+                let (colour, count) = cubes.split_once(':').unwrap();
+                (colour, count.parse::<u32>().unwrap())
+            })
+            .collect();
+        Cubes { counts }
+    }
+
+    fn count(&self, colour: &str) -> u32 {
+        self.counts.get(colour).copied().unwrap_or(0)
+    }
+
+    fn fits_in(&self, bag: &Cubes) -> bool {
+        self.counts
+            .iter()
+            .all(|(colour, count)| *count <= bag.count(colour))
+    }
+
+    fn power(&self, colours: &[&str]) -> u32 {
+        colours.iter().map(|colour| self.count(colour)).product()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Game<'a> {
+    id: u32,
+    draws: Vec<Cubes<'a>>,
+}
+
+impl<'a> Game<'a> {
+    fn parse(line: &'a str) -> Game<'a> {
+        let (game, draws) = line.split_once(':').unwrap();
+        Game {
+            id: game["Game ".len()..].parse::<u32>().unwrap(),
+            draws: draws.split(';').map(Cubes::parse_draw).collect(),
         }
-        valid_game_sum.to_string()
     }
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut power_sum = 0;
-        let matcher = regex::Regex::new(r"(\d+) (green|blue|red)").unwrap();
-
-        for line in input_lines {
-            let parts: Vec<&str> = line.split(":").collect();
-            let colors = matcher.captures_iter(parts[1]);
-            let mut maxes = [0, 0, 0];
-
-            for c in colors {
-                let count = c[1].parse::<u32>().unwrap();
-                match c[2].as_ref() {
-                    "red" => maxes[0] = max(count, maxes[0]),
-                    "green" => maxes[1] = max(count, maxes[1]),
-                    "blue" => maxes[2] = max(count, maxes[2]),
-                    _ => panic!("Unknown color"),
-                }
+    fn minimal_bag(&self) -> Cubes<'a> {
+        let mut bag = Cubes::default();
+        for draw in &self.draws {
+            for (colour, count) in &draw.counts {
+                let bag_count = bag.counts.entry(colour).or_default();
+                *bag_count = max(*bag_count, *count);
             }
-
-            power_sum += maxes[0] * maxes[1] * maxes[2];
         }
-        power_sum.to_string()
+        bag
+    }
+
+    fn is_possible_with(&self, bag: &Cubes) -> bool {
+        self.minimal_bag().fits_in(bag)
     }
 }
 
+fn possible_games<'a, 'b>(
+    games: &'b [Game<'a>],
+    bag: &'b Cubes,
+) -> impl Iterator<Item = &'b Game<'a>> {
+    games.iter().filter(|game| game.is_possible_with(bag))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Solver;
@@ -81,4 +148,58 @@ Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
 Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
         assert_eq!(super::Solver2.part2(sample_input.lines()), "2286");
     }
+
+    const SAMPLE_INPUT: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+    #[test]
+    fn other_bags() {
+        use super::{possible_games, Cubes, Game};
+        use crate::params::Params;
+
+        let games: Vec<Game> = SAMPLE_INPUT.lines().map(Game::parse).collect();
+        let possible_ids = |bag: &str| {
+            possible_games(&games, &Cubes::parse_bag(bag))
+                .map(|game| game.id)
+                .collect::<Vec<u32>>()
+        };
+
+        assert_eq!(possible_ids("red:12,green:13,blue:14"), vec![1, 2, 5]);
+        assert_eq!(possible_ids("red:20,green:13,blue:15"), vec![1, 2, 3, 4, 5]);
+        assert_eq!(possible_ids("red:6,green:3,blue:6"), vec![1, 2, 5]);
+        // Colours missing from the bag can't be drawn at all
+        assert_eq!(possible_ids("red:100,green:100"), vec![]);
+        assert_eq!(
+            possible_ids("red:100,green:100,blue:100,purple:1"),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        let mut params = Params::new();
+        params.insert("bag=red:6,green:3,blue:6");
+        assert_eq!(
+            super::Solver2.part1_with_params(SAMPLE_INPUT.lines(), &params),
+            "8"
+        );
+
+        // Power of just the red and blue cubes
+        params.insert("bag=red:1,blue:1");
+        assert_eq!(
+            super::Solver2.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "370"
+        );
+    }
+
+    #[test]
+    fn other_colours() {
+        use super::{Cubes, Game};
+
+        let game = Game::parse("Game 7: 2 purple, 1 red; 5 purple");
+        assert_eq!(game.minimal_bag(), Cubes::parse_bag("purple:5,red:1"));
+        assert!(game.is_possible_with(&Cubes::parse_bag("purple:5,red:1")));
+        assert!(!game.is_possible_with(&Cubes::parse_bag("red:12,green:13,blue:14")));
+        assert_eq!(game.minimal_bag().power(&["purple", "red", "blue"]), 0);
+    }
 }
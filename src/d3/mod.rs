@@ -1,23 +1,23 @@
-use std::{
-    cmp::{max, min},
-    str::Lines,
-};
+use std::{collections::HashMap, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 
 pub struct Solver3;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Number {
     value: u32,
+    row: usize,
     start: usize,
     end: usize,
 }
 
 impl Number {
-    fn new(start: usize, d: u32) -> Number {
+    fn new(row: usize, start: usize, d: u32) -> Number {
         Number {
             value: d,
+            row,
             start,
             end: start,
         }
@@ -25,29 +25,36 @@ impl Number {
     fn append_digit(self, d: u32) -> Self {
         Number {
             value: self.value * 10 + d,
-            start: self.start,
             end: self.end + 1,
+            ..self
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    symbol: char,
+    row: usize,
+    col: usize,
+}
+
 enum LineState {
     Number(Number),
     Other,
 }
 
 impl LineState {
-    fn append(self, ix: usize, c: char) -> (Self, Option<Number>) {
+    fn append(self, row: usize, ix: usize, c: char) -> (Self, Option<Number>) {
         let c_type = CharType::from_char(&c);
 
         match self {
             Self::Number(n) => match c_type {
                 CharType::Numeric(d) => (Self::Number(n.append_digit(d)), None),
-                CharType::Dot | CharType::Symbol | CharType::Star => (Self::Other, Some(n)),
+                CharType::Dot | CharType::Symbol => (Self::Other, Some(n)),
             },
             Self::Other => match c_type {
-                CharType::Numeric(d) => (Self::Number(Number::new(ix, d)), None),
-                CharType::Dot | CharType::Symbol | CharType::Star => (Self::Other, None),
+                CharType::Numeric(d) => (Self::Number(Number::new(row, ix, d)), None),
+                CharType::Dot | CharType::Symbol => (Self::Other, None),
             },
         }
     }
@@ -56,7 +63,6 @@ impl LineState {
 #[derive(PartialEq, Debug)]
 enum CharType {
     Numeric(u32),
-    Star,
     Symbol,
     Dot,
 }
@@ -65,152 +71,148 @@ impl CharType {
     fn from_char(c: &char) -> CharType {
         match c {
             '0'..='9' => CharType::Numeric(c.to_digit(10).unwrap()),
-            '*' => CharType::Star,
             '.' => CharType::Dot,
             _ => CharType::Symbol,
         }
     }
 }
 
-impl Solver for Solver3 {
-    fn day_number(&self) -> u32 {
-        3
-    }
+// Numbers and symbols are indexed by the cells they cover, to look up what's next to what
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    number_at: HashMap<(usize, usize), usize>,
+    symbol_at: HashMap<(usize, usize), usize>,
+}
 
-    fn part1(&self, input_lines: Lines) -> String {
-        let mut all_numbers: Vec<Vec<Number>> = Vec::new();
-        let mut all_char_types: Vec<Vec<CharType>> = Vec::new();
+impl Schematic {
+    fn parse(input_lines: Lines) -> Schematic {
+        let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
 
-        for line in input_lines {
-            let mut line_numbers: Vec<Number> = Vec::new();
-            let mut line_char_types: Vec<CharType> = Vec::new();
+        for (row, line) in input_lines.enumerate() {
             let mut line_state = LineState::Other;
 
-            for (ix, c) in line.char_indices() {
-                let (l, completed_number) = line_state.append(ix, c);
+            for (col, c) in line.char_indices() {
+                let (l, completed_number) = line_state.append(row, col, c);
                 line_state = l;
 
                 if let Some(n) = completed_number {
-                    line_numbers.push(n)
+                    numbers.push(n)
                 }
 
-                line_char_types.push(CharType::from_char(&c));
+                if CharType::from_char(&c) == CharType::Symbol {
+                    symbols.push(Symbol {
+                        symbol: c,
+                        row,
+                        col,
+                    });
+                }
             }
 
             // Handle the case where a number finishes at the end of a line
             if let LineState::Number(n) = line_state {
-                line_numbers.push(n);
+                numbers.push(n);
             }
-
-            all_numbers.push(line_numbers);
-            all_char_types.push(line_char_types);
         }
 
-        let mut sum = 0;
-        let num_lines = all_char_types.len();
-        let line_length = all_char_types[0].len();
-
-        for (line_num, numbers) in all_numbers.iter().enumerate() {
-            let start_line_ix = max(line_num, 1) - 1;
-            let end_line_ix = min(line_num + 1, num_lines - 1);
-
-            for num in numbers {
-                for line_ix in start_line_ix..=end_line_ix {
-                    if find_touching_symbols(num, &all_char_types[line_ix], line_length) {
-                        sum += num.value;
-                        continue;
-                    }
-                }
+        let mut number_at = HashMap::new();
+        for (ix, n) in numbers.iter().enumerate() {
+            for col in n.start..=n.end {
+                number_at.insert((n.row, col), ix);
             }
         }
 
-        sum.to_string()
+        let symbol_at = symbols
+            .iter()
+            .enumerate()
+            .map(|(ix, s)| ((s.row, s.col), ix))
+            .collect();
+
+        Schematic {
+            numbers,
+            symbols,
+            number_at,
+            symbol_at,
+        }
     }
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut all_numbers: Vec<Vec<Number>> = Vec::new();
-        let mut all_char_types: Vec<Vec<CharType>> = Vec::new();
-
-        for line in input_lines {
-            let mut line_numbers: Vec<Number> = Vec::new();
-            let mut line_char_types: Vec<CharType> = Vec::new();
-            let mut line_state = LineState::Other;
-
-            for (ix, c) in line.char_indices() {
-                let (l, completed_number) = line_state.append(ix, c);
-                line_state = l;
-
-                if let Some(n) = completed_number {
-                    line_numbers.push(n)
-                }
-
-                line_char_types.push(CharType::from_char(&c));
-            }
-
-            // Handle the case where a number finishes at the end of a line
-            if let LineState::Number(n) = line_state {
-                line_numbers.push(n);
-            }
-
-            all_numbers.push(line_numbers);
-            all_char_types.push(line_char_types);
-        }
+    // Including diagonally, and each listed once
+    fn numbers_touching_symbol(&self, symbol_ix: usize) -> Vec<usize> {
+        let symbol = &self.symbols[symbol_ix];
+        let mut touching = Vec::new();
 
-        let mut sum = 0;
-        let num_lines = all_char_types.len();
-        let line_length = all_char_types[0].len();
-
-        for (line_num, c_types) in all_char_types.iter().enumerate() {
-            let start_line_ix = max(line_num, 1) - 1;
-            let end_line_ix = min(line_num + 1, num_lines - 1);
-
-            for (c_ix, c) in c_types.iter().enumerate() {
-                if let CharType::Star = c {
-                    let mut touching_nums = Vec::new();
-                    for line_ix in start_line_ix..=end_line_ix {
-                        touching_nums.extend(find_all_touching_numbers(
-                            c_ix,
-                            &all_numbers[line_ix],
-                            line_length,
-                        ));
-                    }
-                    if touching_nums.len() == 2 {
-                        sum += touching_nums[0] * touching_nums[1];
+        for row in symbol.row.saturating_sub(1)..=symbol.row + 1 {
+            for col in symbol.col.saturating_sub(1)..=symbol.col + 1 {
+                if let Some(ix) = self.number_at.get(&(row, col)) {
+                    if !touching.contains(ix) {
+                        touching.push(*ix);
                     }
                 }
             }
         }
 
-        sum.to_string()
+        touching
     }
-}
 
-fn find_touching_symbols(num: &Number, symbols: &Vec<CharType>, line_length: usize) -> bool {
-    let start_ix = max(num.start, 1) - 1;
-    let end_ix = min(num.end + 1, line_length - 1);
+    fn symbols_touching_number(&self, number_ix: usize) -> Vec<usize> {
+        let number = &self.numbers[number_ix];
 
-    for ix in start_ix..=end_ix {
-        if symbols[ix] == CharType::Symbol || symbols[ix] == CharType::Star {
-            return true;
-        }
+        (number.row.saturating_sub(1)..=number.row + 1)
+            .flat_map(|row| {
+                (number.start.saturating_sub(1)..=number.end + 1).map(move |col| (row, col))
+            })
+            .filter_map(|cell| self.symbol_at.get(&cell).copied())
+            .collect()
+    }
+
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        (0..self.numbers.len())
+            .filter(|ix| !self.symbols_touching_number(*ix).is_empty())
+            .map(|ix| &self.numbers[ix])
     }
 
-    false
+    // For each `symbol` with exactly `num_parts` numbers next to it
+    fn gear_ratios(&self, symbol: char, num_parts: usize) -> impl Iterator<Item = u64> + '_ {
+        (0..self.symbols.len())
+            .filter(move |ix| self.symbols[*ix].symbol == symbol)
+            .map(|ix| self.numbers_touching_symbol(ix))
+            .filter(move |touching| touching.len() == num_parts)
+            .map(|touching| {
+                touching
+                    .iter()
+                    .map(|ix| self.numbers[*ix].value as u64)
+                    .product()
+            })
+    }
 }
 
-fn find_all_touching_numbers(c_ix: usize, numbers: &Vec<Number>, line_length: usize) -> Vec<u32> {
-    let mut touching_nums = Vec::new();
+impl Solver for Solver3 {
+    fn day_number(&self) -> u32 {
+        3
+    }
 
-    let start_ix = max(c_ix, 1) - 1;
-    let end_ix = min(c_ix + 1, line_length - 1);
+    fn part1(&self, input_lines: Lines) -> String {
+        Schematic::parse(input_lines)
+            .part_numbers()
+            .map(|n| n.value)
+            .sum::<u32>()
+            .to_string()
+    }
 
-    for num in numbers {
-        if num.start <= end_ix && num.end >= start_ix {
-            touching_nums.push(num.value);
-        }
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
     }
 
-    touching_nums
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let symbol = params.get_or("gear_symbol", '*');
+        let num_parts = params.get_or("gear_parts", 2);
+
+        Schematic::parse(input_lines)
+            .gear_ratios(symbol, num_parts)
+            .sum::<u64>()
+            .to_string()
+    }
 }
 
 #[cfg(test)]
@@ -244,4 +246,74 @@ mod tests {
 .664.598..";
         assert_eq!(super::Solver3.part2(sample_input.lines()), "467835");
     }
+
+    const SAMPLE_INPUT: &str = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..";
+
+    #[test]
+    fn lookups() {
+        let schematic = super::Schematic::parse(SAMPLE_INPUT.lines());
+        let value_of = |ix: &usize| schematic.numbers[*ix].value;
+        let symbol_of = |ix: &usize| schematic.symbols[*ix].symbol;
+
+        // The '*' on the second row
+        let star = schematic.symbol_at[&(1, 3)];
+        let mut touching: Vec<u32> = schematic
+            .numbers_touching_symbol(star)
+            .iter()
+            .map(value_of)
+            .collect();
+        touching.sort();
+        assert_eq!(touching, vec![35, 467]);
+
+        // 592 is only next to the '+'
+        let number = schematic.number_at[&(6, 3)];
+        assert_eq!(value_of(&number), 592);
+        let touching: Vec<char> = schematic
+            .symbols_touching_number(number)
+            .iter()
+            .map(symbol_of)
+            .collect();
+        assert_eq!(touching, vec!['+']);
+
+        // 114 isn't next to anything
+        let number = schematic.number_at[&(0, 6)];
+        assert!(schematic.symbols_touching_number(number).is_empty());
+    }
+
+    #[test]
+    fn other_gears() {
+        use crate::params::Params;
+
+        let schematic = super::Schematic::parse(SAMPLE_INPUT.lines());
+        assert_eq!(
+            schematic.gear_ratios('*', 1).collect::<Vec<u64>>(),
+            vec![617]
+        );
+        assert_eq!(schematic.gear_ratios('#', 2).collect::<Vec<u64>>(), vec![]);
+        assert_eq!(
+            schematic.gear_ratios('#', 1).collect::<Vec<u64>>(),
+            vec![633]
+        );
+        assert_eq!(
+            schematic.gear_ratios('$', 1).collect::<Vec<u64>>(),
+            vec![664]
+        );
+
+        let mut params = Params::new();
+        params.insert("gear_symbol=$");
+        params.insert("gear_parts=1");
+        assert_eq!(
+            super::Solver3.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "664"
+        );
+    }
 }
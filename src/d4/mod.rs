@@ -1,79 +1,81 @@
 use std::str::Lines;
 
+use crate::parse::unsigned_numbers;
 use crate::Solver;
 
 pub struct Solver4;
 
-impl Solver for Solver4 {
-    fn day_number(&self) -> u32 {
-        4
-    }
-
-    fn part1(&self, input_lines: Lines) -> String {
-        let mut total = 0;
-        let number_matcher = regex::Regex::new(r"\d+").unwrap();
-
-        for line in input_lines {
-            let mut numbers = line.split(':').last().unwrap().split('|');
-
-            let winning_numbers = numbers.next().unwrap();
-            let winning_numbers = number_matcher
-                .find_iter(winning_numbers)
-                .map(|n| n.as_str());
+// Numbers are stored as bitsets, with bit n set if n is on the card
+#[derive(Debug, PartialEq)]
+struct Card {
+    id: u32,
+    winning: u128,
+    held: u128,
+}
 
-            let card_numbers = numbers.next().unwrap();
-            let card_numbers: Vec<&str> = number_matcher
-                .find_iter(card_numbers)
-                .map(|n| n.as_str())
-                .collect();
+impl Card {
+    fn parse(line: &str) -> Card {
+        let (id, numbers) = line.split_once(':').unwrap();
+        let (winning, held) = numbers.split_once('|').unwrap();
 
-            let number_of_matches: u32 =
-                winning_numbers.filter(|n| card_numbers.contains(n)).count() as u32;
+        Card {
+            id: unsigned_numbers(id).next().unwrap(),
+            winning: to_bitset(winning),
+            held: to_bitset(held),
+        }
+    }
 
-            if number_of_matches == 0 {
-                continue;
-            }
+    fn number_of_matches(&self) -> u32 {
+        (self.winning & self.held).count_ones()
+    }
 
-            total += u32::pow(2, number_of_matches - 1);
+    fn points(&self) -> u32 {
+        match self.number_of_matches() {
+            0 => 0,
+            n => u32::pow(2, n - 1),
         }
-
-        total.to_string()
     }
+}
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut card_count: Vec<usize> = Vec::new();
+fn to_bitset(numbers: &str) -> u128 {
+    unsigned_numbers::<u32>(numbers).fold(0, |bits, n| {
+        assert!(n < 128, "Card numbers must be less than 128, not {n}");
+        bits | 1 << n
+    })
+}
 
-        let number_matcher = regex::Regex::new(r"\d+").unwrap();
+fn copy_counts(cards: &[Card]) -> Vec<usize> {
+    let mut card_count: Vec<usize> = Vec::new();
 
-        for (line_num, line) in input_lines.enumerate() {
-            push_or_add_to_vec(&mut card_count, line_num, 1);
+    for (card_ix, card) in cards.iter().enumerate() {
+        push_or_add_to_vec(&mut card_count, card_ix, 1);
 
-            let mut numbers = line.split(':').last().unwrap().split('|');
+        let win_count = card_count[card_ix];
 
-            let winning_numbers = numbers.next().unwrap();
-            let winning_numbers = number_matcher
-                .find_iter(winning_numbers)
-                .map(|n| n.as_str());
+        for won_card_ix in card_ix + 1..=card_ix + card.number_of_matches() as usize {
+            push_or_add_to_vec(&mut card_count, won_card_ix, win_count);
+        }
+    }
 
-            let card_numbers = numbers.next().unwrap();
-            let card_numbers: Vec<&str> = number_matcher
-                .find_iter(card_numbers)
-                .map(|n| n.as_str())
-                .collect();
+    card_count
+}
 
-            let number_of_matches: u32 =
-                winning_numbers.filter(|n| card_numbers.contains(n)).count() as u32;
+impl Solver for Solver4 {
+    fn day_number(&self) -> u32 {
+        4
+    }
 
-            let win_count = card_count[line_num];
+    fn part1(&self, input_lines: Lines) -> String {
+        input_lines
+            .map(|line| Card::parse(line).points())
+            .sum::<u32>()
+            .to_string()
+    }
 
-            for won_card_ix in line_num + 1..=line_num + number_of_matches as usize {
-                push_or_add_to_vec(&mut card_count, won_card_ix, win_count);
-            }
-        }
+    fn part2(&self, input_lines: Lines) -> String {
+        let cards: Vec<Card> = input_lines.map(Card::parse).collect();
 
-        let mut count = 0;
-        card_count.into_iter().for_each(|n| count += n);
-        count.to_string()
+        copy_counts(&cards).iter().sum::<usize>().to_string()
     }
 }
 
@@ -108,4 +110,31 @@ Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
         assert_eq!(super::Solver4.part2(sample_input.lines()), "30");
     }
+
+    #[test]
+    fn cards() {
+        use super::{copy_counts, Card};
+
+        let sample_input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+        let cards: Vec<Card> = sample_input.lines().map(Card::parse).collect();
+
+        assert_eq!(cards[2].id, 3);
+        assert_eq!(
+            cards[2].winning,
+            1 << 1 | 1 << 21 | 1 << 53 | 1 << 59 | 1 << 44
+        );
+        assert_eq!(
+            cards
+                .iter()
+                .map(Card::number_of_matches)
+                .collect::<Vec<u32>>(),
+            vec![4, 2, 2, 1, 0, 0]
+        );
+        assert_eq!(copy_counts(&cards), vec![1, 2, 4, 8, 14, 1]);
+    }
 }
@@ -1,212 +1,204 @@
-use std::str::Lines;
+use std::{
+    cmp::{max, min},
+    str::Lines,
+};
 
+use crate::parse::{sections, unsigned_numbers};
 use crate::Solver;
 
 pub struct Solver5;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Mapping {
     from: i64,
     to: i64,
     delta: i64,
 }
 
-#[derive(Debug)]
+impl Mapping {
+    fn from_line(line: &str) -> Mapping {
+        let mut nums = unsigned_numbers::<i64>(line);
+        let dest_range_start = nums.next().unwrap();
+        let source_range_start = nums.next().unwrap();
+        let range_len = nums.next().unwrap();
+
+        Mapping {
+            from: source_range_start,
+            to: source_range_start + range_len - 1,
+            delta: dest_range_start - source_range_start,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct SeedRange {
     start: i64,
     count: i64,
 }
 
-impl Solver for Solver5 {
-    fn day_number(&self) -> u32 {
-        5
-    }
-
-    fn part1(&self, mut input_lines: Lines) -> String {
-        let mut maps: Vec<Vec<Mapping>> = Vec::new();
-
-        let seeds = input_lines.next().unwrap().split(':').last().unwrap();
-
-        let mut seeds: Vec<i64> = seeds
-            .split(' ')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.parse::<i64>().unwrap())
-            .collect();
-
-        let mut map_num = 0;
+// The pieces are sorted and cover every i64, so values the almanac doesn't move have a piece with
+// zero delta
+#[derive(Debug, Clone, PartialEq)]
+struct PiecewiseMap {
+    pieces: Vec<Mapping>,
+}
 
-        for line in input_lines.filter(|l| !l.is_empty()) {
-            if !line.chars().nth(0).unwrap().is_numeric() {
-                map_num += 1;
-                maps.push(Vec::new());
-                continue;
+impl PiecewiseMap {
+    fn from_mappings(mut mappings: Vec<Mapping>) -> PiecewiseMap {
+        mappings.sort_by_key(|m| m.from);
+
+        let mut pieces = Vec::new();
+        let mut next_from = i64::MIN;
+
+        for mapping in mappings {
+            assert!(mapping.from >= next_from, "Overlapping mappings");
+            if mapping.from > next_from {
+                pieces.push(Mapping {
+                    from: next_from,
+                    to: mapping.from - 1,
+                    delta: 0,
+                });
             }
-
-            // else it's a mapping
-            let mut nums = line.split(' ').map(|s| s.parse::<i64>().unwrap());
-            let dest_range_start = nums.next().unwrap();
-            let source_range_start = nums.next().unwrap();
-            let range_len = nums.next().unwrap();
-
-            let new_map = Mapping {
-                from: source_range_start,
-                to: source_range_start + range_len - 1,
-                delta: dest_range_start - source_range_start,
-            };
-
-            maps[map_num - 1].push(new_map);
+            next_from = mapping.to + 1;
+            pieces.push(mapping);
         }
 
-        let mut lowest: Option<i64> = None;
-
-        for seed in &mut seeds {
-            for map in &maps {
-                for mapping in map {
-                    if *seed >= mapping.from && *seed <= mapping.to {
-                        *seed += mapping.delta;
-                        break;
-                    }
-                }
-            }
+        pieces.push(Mapping {
+            from: next_from,
+            to: i64::MAX,
+            delta: 0,
+        });
 
-            if let Some(v) = lowest {
-                if *seed < v {
-                    lowest = Some(*seed);
-                }
-            } else {
-                lowest = Some(*seed);
-            }
-        }
-
-        lowest.unwrap().to_string()
+        PiecewiseMap { pieces }
     }
 
-    fn part2(&self, mut input_lines: Lines) -> String {
-        let mut maps: Vec<Vec<Mapping>> = Vec::new();
-
-        let seeds = input_lines.next().unwrap().split(':').last().unwrap();
-
-        let seeds: Vec<i64> = seeds
-            .split(' ')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.parse::<i64>().unwrap())
-            .collect();
+    // (start, end, delta) for each piece overlapping start..=end
+    fn split(&self, start: i64, end: i64) -> impl Iterator<Item = (i64, i64, i64)> + '_ {
+        let first_piece = self.pieces.partition_point(|p| p.to < start);
+        self.pieces[first_piece..]
+            .iter()
+            .take_while(move |p| p.from <= end)
+            .map(move |p| (max(p.from, start), min(p.to, end), p.delta))
+    }
 
-        let mut next_seed_ranges: Vec<SeedRange> = Vec::new();
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut pieces: Vec<Mapping> = Vec::new();
 
-        for ix in 0..seeds.len() / 2 {
-            let start = seeds[2 * ix];
-            let count = seeds[2 * ix + 1];
-            next_seed_ranges.push(SeedRange { start, count });
-        }
+        for piece in &self.pieces {
+            // Split where this piece's output crosses the pieces of the next map
+            let outputs = next.split(piece.from + piece.delta, piece.to + piece.delta);
 
-        let mut map_num = 0;
+            for (from, to, delta) in outputs {
+                let new_piece = Mapping {
+                    from: from - piece.delta,
+                    to: to - piece.delta,
+                    delta: piece.delta + delta,
+                };
 
-        for line in input_lines.filter(|l| !l.is_empty()) {
-            if !line.chars().nth(0).unwrap().is_numeric() {
-                map_num += 1;
-                maps.push(Vec::new());
-                continue;
+                match pieces.last_mut() {
+                    Some(last) if last.delta == new_piece.delta => last.to = new_piece.to,
+                    _ => pieces.push(new_piece),
+                }
             }
-
-            // else it's a mapping
-            let mut nums = line.split(' ').map(|s| s.parse::<i64>().unwrap());
-            let dest_range_start = nums.next().unwrap();
-            let source_range_start = nums.next().unwrap();
-            let range_len = nums.next().unwrap();
-
-            let new_map = Mapping {
-                from: source_range_start,
-                to: source_range_start + range_len - 1,
-                delta: dest_range_start - source_range_start,
-            };
-
-            maps[map_num - 1].push(new_map);
         }
 
-        // let mut next_seed_ranges: Vec<SeedRange> = Vec::new(); ??
-
-        for map in maps {
-            let mut unmatched_seed_ranges = next_seed_ranges;
-            next_seed_ranges = Vec::new();
-            for mapping in &map {
-                let seed_ranges = unmatched_seed_ranges;
-                unmatched_seed_ranges = Vec::new();
-
-                for sr in seed_ranges {
-                    let sr_end = sr.start + sr.count - 1;
-                    if sr.start < mapping.from {
-                        if sr_end >= mapping.from {
-                            // there's an overlap
-
-                            let num_seeds = mapping.from - sr.start;
-
-                            // seeds before the mapping
-                            unmatched_seed_ranges.push(SeedRange {
-                                start: sr.start,
-                                count: num_seeds,
-                            });
-
-                            let mut remaining = sr.count - num_seeds;
-
-                            if sr_end > mapping.to {
-                                // there are some seeds after the matching set
-                                let num_seeds = sr_end - mapping.to;
+        PiecewiseMap { pieces }
+    }
 
-                                unmatched_seed_ranges.push(SeedRange {
-                                    start: mapping.to + 1,
-                                    count: num_seeds,
-                                });
+    fn apply(&self, value: i64) -> i64 {
+        let (_, _, delta) = self.split(value, value).next().unwrap();
+        value + delta
+    }
 
-                                remaining -= num_seeds;
-                            }
+    fn apply_range(&self, range: SeedRange) -> Vec<SeedRange> {
+        self.split(range.start, range.start + range.count - 1)
+            .map(|(start, end, delta)| SeedRange {
+                start: start + delta,
+                count: end - start + 1,
+            })
+            .collect()
+    }
 
-                            // rest of the range is in the mapping
-                            next_seed_ranges.push(SeedRange {
-                                start: mapping.from + mapping.delta,
-                                count: remaining,
-                            });
-                        } else {
-                            // no overlap
-                            unmatched_seed_ranges.push(sr);
-                        }
-                    } else if sr.start <= mapping.to {
-                        // some overlap
+    #[allow(dead_code)]
+    fn inverse(&self, output: i64) -> Vec<i64> {
+        self.pieces
+            .iter()
+            .map(|p| output - p.delta)
+            .zip(&self.pieces)
+            .filter(|(value, p)| p.from <= *value && *value <= p.to)
+            .map(|(value, _)| value)
+            .collect()
+    }
+}
 
-                        let mut remaining = sr.count;
+struct Almanac {
+    seeds: Vec<i64>,
+    layers: Vec<PiecewiseMap>,
+}
 
-                        if sr_end > mapping.to {
-                            // there are some seeds after the matching set
-                            let num_seeds = sr_end - mapping.to;
+impl Almanac {
+    fn parse(mut input_lines: Lines) -> Almanac {
+        let seeds = unsigned_numbers(input_lines.next().unwrap()).collect();
+
+        let layers = sections(input_lines)
+            .map(|section| {
+                // The first line of each section is just the name of the map
+                PiecewiseMap::from_mappings(
+                    section[1..]
+                        .iter()
+                        .map(|line| Mapping::from_line(line))
+                        .collect(),
+                )
+            })
+            .collect();
 
-                            unmatched_seed_ranges.push(SeedRange {
-                                start: mapping.to + 1,
-                                count: num_seeds,
-                            });
+        Almanac { seeds, layers }
+    }
 
-                            remaining -= num_seeds;
-                        }
+    fn seed_ranges(&self) -> impl Iterator<Item = SeedRange> + '_ {
+        self.seeds.chunks(2).map(|pair| SeedRange {
+            start: pair[0],
+            count: pair[1],
+        })
+    }
 
-                        // rest of the range is in the mapping
-                        next_seed_ranges.push(SeedRange {
-                            start: sr.start + mapping.delta,
-                            count: remaining,
-                        });
-                    } else {
-                        // no overlap
-                        unmatched_seed_ranges.push(sr);
-                    }
-                }
-            }
+    fn seed_to_location(&self) -> PiecewiseMap {
+        let identity = PiecewiseMap::from_mappings(Vec::new());
+        self.layers
+            .iter()
+            .fold(identity, |map, layer| map.then(layer))
+    }
+}
 
-            // handle seed ranges that intersected no mappings
-            next_seed_ranges.append(&mut unmatched_seed_ranges);
-        }
+impl Solver for Solver5 {
+    fn day_number(&self) -> u32 {
+        5
+    }
 
-        let values = next_seed_ranges.iter().map(|sr| sr.start);
-        let lowest = values.min();
+    fn part1(&self, input_lines: Lines) -> String {
+        let almanac = Almanac::parse(input_lines);
+        let seed_to_location = almanac.seed_to_location();
+
+        almanac
+            .seeds
+            .iter()
+            .map(|seed| seed_to_location.apply(*seed))
+            .min()
+            .unwrap()
+            .to_string()
+    }
 
-        lowest.unwrap().to_string()
+    fn part2(&self, input_lines: Lines) -> String {
+        let almanac = Almanac::parse(input_lines);
+        let seed_to_location = almanac.seed_to_location();
+
+        almanac
+            .seed_ranges()
+            .flat_map(|range| seed_to_location.apply_range(range))
+            .map(|range| range.start)
+            .min()
+            .unwrap()
+            .to_string()
     }
 }
 
@@ -287,4 +279,239 @@ humidity-to-location map:
 56 93 4";
         assert_eq!(super::Solver5.part2(sample_input.lines()), "46");
     }
+
+    const SAMPLE_INPUT: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    #[test]
+    fn composed_map_matches_layers() {
+        use super::{Almanac, SeedRange};
+
+        let almanac = Almanac::parse(SAMPLE_INPUT.lines());
+        let seed_to_location = almanac.seed_to_location();
+        let maps = layered::parse_maps(SAMPLE_INPUT);
+
+        for seed in -10..200 {
+            assert_eq!(
+                seed_to_location.apply(seed),
+                layered::location(seed, &maps),
+                "seed {seed}"
+            );
+        }
+
+        for (start, count) in [(79, 14), (55, 13), (0, 100), (-5, 300), (98, 1)] {
+            let range = SeedRange { start, count };
+            let lowest = seed_to_location
+                .apply_range(range)
+                .iter()
+                .map(|r| r.start)
+                .min();
+            assert_eq!(lowest, layered::lowest_location(vec![range], &maps));
+
+            let total: i64 = seed_to_location
+                .apply_range(range)
+                .iter()
+                .map(|r| r.count)
+                .sum();
+            assert_eq!(total, count);
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        use super::Almanac;
+
+        let almanac = Almanac::parse(SAMPLE_INPUT.lines());
+        let seed_to_location = almanac.seed_to_location();
+
+        // The seeds with the lowest locations in each part
+        assert_eq!(seed_to_location.inverse(35), vec![13]);
+        assert_eq!(seed_to_location.inverse(46), vec![82]);
+
+        // The sample's maps are all bijections
+        for location in -10..200 {
+            let seeds = seed_to_location.inverse(location);
+            assert_eq!(seeds.len(), 1);
+            assert_eq!(seed_to_location.apply(seeds[0]), location);
+        }
+    }
+
+    #[test]
+    fn compose() {
+        use super::{Mapping, PiecewiseMap};
+
+        let add_ten = PiecewiseMap::from_mappings(vec![Mapping {
+            from: 0,
+            to: 9,
+            delta: 10,
+        }]);
+        let swap = PiecewiseMap::from_mappings(vec![
+            Mapping {
+                from: 5,
+                to: 14,
+                delta: 10,
+            },
+            Mapping {
+                from: 15,
+                to: 24,
+                delta: -10,
+            },
+        ]);
+
+        let composed = add_ten.then(&swap);
+        for value in -5..30 {
+            assert_eq!(composed.apply(value), swap.apply(add_ten.apply(value)));
+        }
+        // 0..=4 go to 10..=14 then 20..=24, 5..=9 go to 15..=19 then 5..=9, and nothing ends up in
+        // 15..=19
+        assert_eq!(composed.inverse(7), vec![7, 17]);
+        assert_eq!(composed.inverse(17), vec![]);
+        assert_eq!(composed.inverse(22), vec![2, 12]);
+    }
+
+    // Applying the maps one layer at a time
+    mod layered {
+        use super::super::{Mapping, SeedRange};
+        use crate::parse::sections;
+
+        pub fn parse_maps(input: &str) -> Vec<Vec<Mapping>> {
+            let mut input_lines = input.lines();
+            input_lines.next();
+            sections(input_lines)
+                .map(|section| {
+                    section[1..]
+                        .iter()
+                        .map(|line| Mapping::from_line(line))
+                        .collect()
+                })
+                .collect()
+        }
+
+        pub fn location(mut seed: i64, maps: &[Vec<Mapping>]) -> i64 {
+            for map in maps {
+                for mapping in map {
+                    if seed >= mapping.from && seed <= mapping.to {
+                        seed += mapping.delta;
+                        break;
+                    }
+                }
+            }
+            seed
+        }
+
+        pub fn lowest_location(
+            mut next_seed_ranges: Vec<SeedRange>,
+            maps: &[Vec<Mapping>],
+        ) -> Option<i64> {
+            for map in maps {
+                let mut unmatched_seed_ranges = next_seed_ranges;
+                next_seed_ranges = Vec::new();
+                for mapping in map {
+                    let seed_ranges = unmatched_seed_ranges;
+                    unmatched_seed_ranges = Vec::new();
+
+                    for sr in seed_ranges {
+                        let sr_end = sr.start + sr.count - 1;
+                        if sr.start < mapping.from {
+                            if sr_end >= mapping.from {
+                                // there's an overlap
+
+                                let num_seeds = mapping.from - sr.start;
+
+                                // seeds before the mapping
+                                unmatched_seed_ranges.push(SeedRange {
+                                    start: sr.start,
+                                    count: num_seeds,
+                                });
+
+                                let mut remaining = sr.count - num_seeds;
+
+                                if sr_end > mapping.to {
+                                    // there are some seeds after the matching set
+                                    let num_seeds = sr_end - mapping.to;
+
+                                    unmatched_seed_ranges.push(SeedRange {
+                                        start: mapping.to + 1,
+                                        count: num_seeds,
+                                    });
+
+                                    remaining -= num_seeds;
+                                }
+
+                                // rest of the range is in the mapping
+                                next_seed_ranges.push(SeedRange {
+                                    start: mapping.from + mapping.delta,
+                                    count: remaining,
+                                });
+                            } else {
+                                // no overlap
+                                unmatched_seed_ranges.push(sr);
+                            }
+                        } else if sr.start <= mapping.to {
+                            // some overlap
+
+                            let mut remaining = sr.count;
+
+                            if sr_end > mapping.to {
+                                // there are some seeds after the matching set
+                                let num_seeds = sr_end - mapping.to;
+
+                                unmatched_seed_ranges.push(SeedRange {
+                                    start: mapping.to + 1,
+                                    count: num_seeds,
+                                });
+
+                                remaining -= num_seeds;
+                            }
+
+                            // rest of the range is in the mapping
+                            next_seed_ranges.push(SeedRange {
+                                start: sr.start + mapping.delta,
+                                count: remaining,
+                            });
+                        } else {
+                            // no overlap
+                            unmatched_seed_ranges.push(sr);
+                        }
+                    }
+                }
+
+                // handle seed ranges that intersected no mappings
+                next_seed_ranges.append(&mut unmatched_seed_ranges);
+            }
+
+            next_seed_ranges.iter().map(|sr| sr.start).min()
+        }
+    }
 }
@@ -1,5 +1,7 @@
+use num::{integer::Integer, BigInt, One, Zero};
 use std::str::Lines;
 
+use crate::parse::unsigned_numbers;
 use crate::Solver;
 
 pub struct Solver6;
@@ -10,62 +12,58 @@ impl Solver for Solver6 {
     }
 
     fn part1(&self, mut input_lines: Lines) -> String {
-        let number_matcher = regex::Regex::new(r"\d+").unwrap();
-
-        let times: Vec<f64> = number_matcher
-            .find_iter(input_lines.next().unwrap().split(": ").last().unwrap())
-            .map(|n| n.as_str().parse::<i64>().unwrap() as f64)
-            .collect();
-
-        let dists: Vec<f64> = number_matcher
-            .find_iter(input_lines.next().unwrap().split(": ").last().unwrap())
-            .map(|n| n.as_str().parse::<i64>().unwrap() as f64)
-            .collect();
-
-        let mut bounds: Vec<f64> = Vec::new();
-
-        for ix in 0..dists.len() {
-            let t = times[ix];
-            let d = dists[ix];
-            let disc = t * t - (4_f64 * d);
-            let lower_bound = (t - disc.sqrt()) / 2_f64;
-            let upper_bound = (t + disc.sqrt()) / 2_f64;
-            bounds.push(
-                (upper_bound - 0.00000000001).floor() - (lower_bound + 0.0000000000001).ceil()
-                    + 1_f64,
-            );
-        }
-
-        let mut sum = 1_f64;
-        for b in bounds {
-            sum *= b;
-        }
-        sum.to_string()
+        let times = unsigned_numbers::<BigInt>(input_lines.next().unwrap());
+        let records = unsigned_numbers::<BigInt>(input_lines.next().unwrap());
+
+        times
+            .zip(records)
+            .map(|(time, record)| ways_to_win(&time, &record))
+            .product::<BigInt>()
+            .to_string()
     }
 
     fn part2(&self, mut input_lines: Lines) -> String {
-        let mut time: i64 = 0;
-
-        for d in input_lines.next().unwrap().matches(char::is_numeric) {
-            time = time * 10 + d.parse::<i64>().unwrap()
-        }
+        // The spaces between the numbers are just bad kerning, so there's one long race
+        let time = concatenated_digits(input_lines.next().unwrap());
+        let record = concatenated_digits(input_lines.next().unwrap());
 
-        let mut dist: i64 = 0;
-
-        for d in input_lines.next().unwrap().matches(char::is_numeric) {
-            dist = dist * 10 + d.parse::<i64>().unwrap()
-        }
+        ways_to_win(&time, &record).to_string()
+    }
+}
 
-        let time = time as f64;
-        let dist = dist as f64;
+fn concatenated_digits(line: &str) -> BigInt {
+    line.matches(char::is_numeric)
+        .collect::<String>()
+        .parse()
+        .unwrap()
+}
 
-        let disc = time * time - (4_f64 * dist);
-        let lower_bound = (time - disc.sqrt()) / 2_f64;
-        let upper_bound = (time + disc.sqrt()) / 2_f64;
-        let val =
-            (upper_bound - 0.00000000001).floor() - (lower_bound + 0.0000000000001).ceil() + 1_f64;
+// The winning hold times lie strictly between the roots of h^2 - time * h + record.  Kept in
+// integers so that long races can't be off by one.
+fn ways_to_win(time: &BigInt, record: &BigInt) -> BigInt {
+    let disc: BigInt = time * time - record * 4;
+    if disc <= BigInt::zero() {
+        return BigInt::zero();
+    }
 
-        val.to_string()
+    let root = disc.sqrt();
+
+    // Shortest winning hold is floor(lower root) + 1.  If disc isn't a square, the lower root is
+    // strictly between (time - root - 1) / 2 and (time - root) / 2, so the flooring works out as
+    // if the root was one bigger.
+    let lower_root_bound: BigInt = if &root * &root == disc {
+        time - &root
+    } else {
+        time - &root - 1
+    };
+    let shortest = lower_root_bound.div_floor(&BigInt::from(2)) + 1;
+
+    // The winning hold times are symmetric about time / 2
+    let longest = time - &shortest;
+    if longest < shortest {
+        BigInt::zero()
+    } else {
+        longest - shortest + BigInt::one()
     }
 }
 
@@ -84,4 +82,39 @@ Distance: 9 40 200";
 Distance: 9 40 200";
         assert_eq!(super::Solver6.part2(sample_input.lines()), "71503");
     }
+
+    #[test]
+    fn matches_brute_force() {
+        use super::ways_to_win;
+        use num::BigInt;
+
+        for time in 0..60_u64 {
+            for record in 0..=(time * time / 4 + 2) {
+                let expected = (0..=time).filter(|h| h * (time - h) > record).count();
+                assert_eq!(
+                    ways_to_win(&BigInt::from(time), &BigInt::from(record)),
+                    BigInt::from(expected),
+                    "time {time}, record {record}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn long_race() {
+        use super::ways_to_win;
+        use num::BigInt;
+
+        // Far beyond what f64 can hold exactly.  With time = 2n and record = n^2 - k^2, the hold
+        // times n - k + 1 ..= n + k - 1 win.
+        let n: BigInt = "123456789012345678901234567890".parse().unwrap();
+        let k: BigInt = "987654321".parse().unwrap();
+        let time = &n * 2;
+        let record = &n * &n - &k * &k;
+        assert_eq!(ways_to_win(&time, &record), &k * 2 - 1);
+
+        // Just out of reach
+        assert_eq!(ways_to_win(&time, &(&n * &n)), BigInt::from(0));
+        assert_eq!(ways_to_win(&time, &(&n * &n - 1)), BigInt::from(1));
+    }
 }
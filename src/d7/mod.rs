@@ -1,219 +1,206 @@
-use std::{collections::HashMap, str::Lines};
+use itertools::Itertools;
+use std::{cmp::Reverse, collections::HashMap, str::Lines};
 
+use crate::params::Params;
 use crate::Solver;
 
 pub struct Solver7;
 
-#[derive(PartialEq, Eq, Debug)]
-struct Hand {
-    quality: i32,
-    bet: usize,
-}
+impl Solver for Solver7 {
+    fn day_number(&self) -> u32 {
+        7
+    }
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    fn part1(&self, input_lines: Lines) -> String {
+        self.part1_with_params(input_lines, &Params::new())
+    }
+
+    fn part2(&self, input_lines: Lines) -> String {
+        self.part2_with_params(input_lines, &Params::new())
     }
-}
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.quality.cmp(&other.quality)
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let rules = rule_set(params.get("rules").unwrap_or("standard"));
+        let hands: Vec<Hand> = input_lines.map(Hand::parse).collect();
+        total_winnings(&hands, rules.as_ref()).to_string()
+    }
+
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let rules = rule_set(params.get("rules").unwrap_or("jokers"));
+        let hands: Vec<Hand> = input_lines.map(Hand::parse).collect();
+        total_winnings(&hands, rules.as_ref()).to_string()
     }
 }
 
-impl Solver for Solver7 {
-    fn day_number(&self) -> u32 {
-        7
+// Weakest first, so that the derived ordering ranks them
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
+
+impl HandType {
+    // Counts of each kind of card, most common first
+    fn from_counts(counts: &[usize]) -> HandType {
+        match counts {
+            [5, ..] => HandType::FiveOfAKind,
+            [4, ..] => HandType::FourOfAKind,
+            [3, 2, ..] => HandType::FullHouse,
+            [3, ..] => HandType::ThreeOfAKind,
+            [2, 2, ..] => HandType::TwoPair,
+            [2, ..] => HandType::OnePair,
+            _ => HandType::HighCard,
+        }
     }
+}
 
-    fn part1(&self, input_lines: Lines) -> String {
-        let mut fives = Vec::<Hand>::new();
-        let mut fours = Vec::<Hand>::new();
-        let mut full_houses = Vec::<Hand>::new();
-        let mut threes = Vec::<Hand>::new();
-        let mut two_pairs = Vec::<Hand>::new();
-        let mut one_pairs = Vec::<Hand>::new();
-        let mut high_cards = Vec::<Hand>::new();
-
-        for line in input_lines {
-            let mut parts = line.split(' ');
-            let cards = parts.next().unwrap().chars().map(|c| {
-                if c.is_numeric() {
-                    c.to_digit(10).unwrap() as i32
-                } else {
-                    match c {
-                        'T' => 10,
-                        'J' => 11,
-                        'Q' => 12,
-                        'K' => 13,
-                        'A' => 14,
-                        _ => panic!("Unknown card"),
-                    }
-                }
-            });
-            let bet = parts.next().unwrap().parse::<usize>().unwrap();
-
-            let mut card_counts: HashMap<i32, i32> = HashMap::new();
-            let mut quality = 0;
-
-            for card in cards {
-                quality = quality * 15 + card;
-                match card_counts.get_mut(&card) {
-                    Some(count) => *count += 1,
-                    None => {
-                        card_counts.insert(card, 1);
-                    }
-                };
-            }
+// Hands are ranked by type first, then by the values of their cards in order
+trait RuleSet {
+    fn card_value(&self, card: char) -> u8;
 
-            let mut ordered_cards = card_counts.values().collect::<Vec<&i32>>();
-            ordered_cards.sort();
-
-            match (ordered_cards.pop(), ordered_cards.pop()) {
-                (Some(5), _) => fives.push(Hand { quality, bet }),
-                (Some(4), _) => fours.push(Hand { quality, bet }),
-                (Some(3), Some(2)) => full_houses.push(Hand { quality, bet }),
-                (Some(3), _) => threes.push(Hand { quality, bet }),
-                (Some(2), Some(2)) => two_pairs.push(Hand { quality, bet }),
-                (Some(2), _) => one_pairs.push(Hand { quality, bet }),
-                _ => high_cards.push(Hand { quality, bet }),
+    fn is_wild(&self, _card: char) -> bool {
+        false
+    }
+
+    fn hand_type(&self, cards: &str) -> HandType {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        let mut wilds = 0;
+        for card in cards.chars() {
+            if self.is_wild(card) {
+                wilds += 1;
+            } else {
+                *counts.entry(card).or_default() += 1;
             }
         }
 
-        fives.sort();
-        fours.sort();
-        full_houses.sort();
-        threes.sort();
-        two_pairs.sort();
-        one_pairs.sort();
-        high_cards.sort();
-
-        let mut total_hands = fives.len()
-            + fours.len()
-            + full_houses.len()
-            + threes.len()
-            + two_pairs.len()
-            + one_pairs.len()
-            + high_cards.len();
-
-        let ordered_hands = vec![
-            fives,
-            fours,
-            full_houses,
-            threes,
-            two_pairs,
-            one_pairs,
-            high_cards,
-        ];
-
-        let mut sum = 0;
-
-        for mut hand_type in ordered_hands {
-            hand_type.reverse();
-            for hand in &hand_type {
-                sum += hand.bet * total_hands;
-                total_hands -= 1;
-            }
+        // The wild cards are always best used as more of the most common card
+        let mut counts: Vec<usize> = counts
+            .into_values()
+            .sorted_by_key(|c| Reverse(*c))
+            .collect();
+        match counts.first_mut() {
+            Some(most) => *most += wilds,
+            None => counts.push(wilds),
         }
 
-        sum.to_string()
+        HandType::from_counts(&counts)
     }
+}
 
-    fn part2(&self, input_lines: Lines) -> String {
-        let mut fives = Vec::<Hand>::new();
-        let mut fours = Vec::<Hand>::new();
-        let mut full_houses = Vec::<Hand>::new();
-        let mut threes = Vec::<Hand>::new();
-        let mut two_pairs = Vec::<Hand>::new();
-        let mut one_pairs = Vec::<Hand>::new();
-        let mut high_cards = Vec::<Hand>::new();
-
-        for line in input_lines {
-            let mut parts = line.split(' ');
-            let vals = parts.next().unwrap();
-
-            let cards = vals.chars().map(|c| {
-                if c.is_numeric() {
-                    c.to_digit(10).unwrap() as i32
-                } else {
-                    match c {
-                        'T' => 10,
-                        'J' => 1,
-                        'Q' => 12,
-                        'K' => 13,
-                        'A' => 14,
-                        _ => panic!("Unknown card"),
-                    }
-                }
-            });
-            let bet = parts.next().unwrap().parse::<usize>().unwrap();
-
-            let mut card_counts: HashMap<i32, i32> = HashMap::new();
-            let mut quality = 0;
-
-            for card in cards {
-                quality = quality * 15 + card;
-                match card_counts.get_mut(&card) {
-                    Some(count) => *count += 1,
-                    None => {
-                        card_counts.insert(card, 1);
-                    }
-                };
-            }
+fn standard_card_value(card: char) -> u8 {
+    match card {
+        '2'..='9' => card.to_digit(10).unwrap() as u8,
+        'T' => 10,
+        'J' => 11,
+        'Q' => 12,
+        'K' => 13,
+        'A' => 14,
+        _ => panic!("Unknown card {card}"),
+    }
+}
 
-            let num_jokers = card_counts.remove(&1).unwrap_or(0);
+struct Standard;
 
-            let mut ordered_cards = card_counts.values().cloned().collect::<Vec<i32>>();
-            ordered_cards.sort();
+impl RuleSet for Standard {
+    fn card_value(&self, card: char) -> u8 {
+        standard_card_value(card)
+    }
+}
 
-            let most = ordered_cards.pop().unwrap_or(0) + num_jokers;
-            ordered_cards.push(most);
+// J is wild, but worth the least on its own
+struct Jokers;
 
-            match (ordered_cards.pop(), ordered_cards.pop()) {
-                (Some(5), _) => fives.push(Hand { quality, bet }),
-                (Some(4), _) => fours.push(Hand { quality, bet }),
-                (Some(3), Some(2)) => full_houses.push(Hand { quality, bet }),
-                (Some(3), _) => threes.push(Hand { quality, bet }),
-                (Some(2), Some(2)) => two_pairs.push(Hand { quality, bet }),
-                (Some(2), _) => one_pairs.push(Hand { quality, bet }),
-                _ => high_cards.push(Hand { quality, bet }),
-            }
+impl RuleSet for Jokers {
+    fn card_value(&self, card: char) -> u8 {
+        match card {
+            'J' => 1,
+            _ => standard_card_value(card),
         }
+    }
 
-        let mut total_hands = fives.len()
-            + fours.len()
-            + full_houses.len()
-            + threes.len()
-            + two_pairs.len()
-            + one_pairs.len()
-            + high_cards.len();
-
-        let ordered_hands = vec![
-            fives,
-            fours,
-            full_houses,
-            threes,
-            two_pairs,
-            one_pairs,
-            high_cards,
-        ];
-
-        let mut sum = 0;
-
-        for mut hand_type in ordered_hands {
-            hand_type.sort();
-            hand_type.reverse();
-            for hand in &hand_type {
-                sum += hand.bet * total_hands;
-                total_hands -= 1;
-            }
+    fn is_wild(&self, card: char) -> bool {
+        card == 'J'
+    }
+}
+
+// 2 is wild, and is still the lowest card on its own
+struct WildDeuces;
+
+impl RuleSet for WildDeuces {
+    fn card_value(&self, card: char) -> u8 {
+        standard_card_value(card)
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        card == '2'
+    }
+}
+
+// A straight beats three of a kind but loses to a full house.  Aces are only high.
+struct Straights;
+
+impl RuleSet for Straights {
+    fn card_value(&self, card: char) -> u8 {
+        standard_card_value(card)
+    }
+
+    fn hand_type(&self, cards: &str) -> HandType {
+        let values: Vec<u8> = cards.chars().map(|c| self.card_value(c)).sorted().collect();
+        let consecutive = values.len() == 5 && values.windows(2).all(|w| w[1] == w[0] + 1);
+
+        if consecutive {
+            HandType::Straight
+        } else {
+            Standard.hand_type(cards)
         }
+    }
+}
 
-        sum.to_string()
+fn rule_set(name: &str) -> Box<dyn RuleSet> {
+    match name {
+        "standard" => Box::new(Standard),
+        "jokers" => Box::new(Jokers),
+        "wild-deuces" => Box::new(WildDeuces),
+        "straights" => Box::new(Straights),
+        _ => panic!("Unknown rule set {name}"),
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct Hand<'a> {
+    cards: &'a str,
+    bet: usize,
+}
+
+impl<'a> Hand<'a> {
+    fn parse(line: &'a str) -> Hand<'a> {
+        let (cards, bet) = line.split_once(' ').unwrap();
+        Hand {
+            cards,
+            bet: bet.parse().unwrap(),
+        }
+    }
+}
+
+fn total_winnings(hands: &[Hand], rules: &dyn RuleSet) -> usize {
+    hands
+        .iter()
+        .map(|hand| {
+            let values: Vec<u8> = hand.cards.chars().map(|c| rules.card_value(c)).collect();
+            ((rules.hand_type(hand.cards), values), hand.bet)
+        })
+        .sorted()
+        .enumerate()
+        .map(|(ix, (_, bet))| (ix + 1) * bet)
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Solver;
@@ -235,4 +222,73 @@ KTJJT 220
 QQQJA 483";
         assert_eq!(super::Solver7.part2(sample_input.lines()), "5905");
     }
+
+    const SAMPLE_INPUT: &str = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483";
+
+    #[test]
+    fn hand_types() {
+        use super::{HandType, Jokers, RuleSet, Standard, Straights, WildDeuces};
+
+        assert_eq!(Standard.hand_type("32T3K"), HandType::OnePair);
+        assert_eq!(Standard.hand_type("KTJJT"), HandType::TwoPair);
+        assert_eq!(Standard.hand_type("23332"), HandType::FullHouse);
+        assert_eq!(Standard.hand_type("23456"), HandType::HighCard);
+
+        assert_eq!(Jokers.hand_type("KTJJT"), HandType::FourOfAKind);
+        assert_eq!(Jokers.hand_type("JJJJJ"), HandType::FiveOfAKind);
+        assert_eq!(Jokers.hand_type("2345J"), HandType::OnePair);
+
+        assert_eq!(WildDeuces.hand_type("2233K"), HandType::FourOfAKind);
+        assert_eq!(WildDeuces.hand_type("22222"), HandType::FiveOfAKind);
+        assert_eq!(WildDeuces.hand_type("KTJJT"), HandType::TwoPair);
+
+        assert_eq!(Straights.hand_type("23456"), HandType::Straight);
+        assert_eq!(Straights.hand_type("QJAKT"), HandType::Straight);
+        assert_eq!(Straights.hand_type("2345A"), HandType::HighCard);
+        assert_eq!(Straights.hand_type("KKK23"), HandType::ThreeOfAKind);
+    }
+
+    #[test]
+    fn rule_set_params() {
+        use crate::params::Params;
+
+        let mut params = Params::new();
+        params.insert("rules=jokers");
+        assert_eq!(
+            super::Solver7.part1_with_params(SAMPLE_INPUT.lines(), &params),
+            "5905"
+        );
+
+        params.insert("rules=standard");
+        assert_eq!(
+            super::Solver7.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            "6440"
+        );
+
+        // A straight beats three of a kind but not a full house, and is only a high card
+        // otherwise
+        let sample_input = "23456 1
+33344 2
+KKK23 3";
+        assert_eq!(
+            super::Solver7.part1_with_params(sample_input.lines(), &params),
+            "13"
+        );
+        params.insert("rules=straights");
+        assert_eq!(
+            super::Solver7.part1_with_params(sample_input.lines(), &params),
+            "11"
+        );
+
+        // With wild deuces, 23456 is only a pair and KKK23 is four of a kind
+        params.insert("rules=wild-deuces");
+        assert_eq!(
+            super::Solver7.part1_with_params(sample_input.lines(), &params),
+            "14"
+        );
+    }
 }
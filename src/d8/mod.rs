@@ -1,6 +1,8 @@
-use num::integer;
+use itertools::Itertools;
+use num::integer::{ExtendedGcd, Integer};
 use std::{collections::HashMap, str::Lines};
 
+use crate::graph::Graph;
 use crate::Solver;
 
 pub struct Solver8;
@@ -14,22 +16,15 @@ impl Solver for Solver8 {
         let directions = input_lines.next().unwrap().chars().collect::<Vec<char>>();
         let _ = input_lines.next();
 
-        let mut network: HashMap<&str, (&str, &str)> = HashMap::new();
-        for line in input_lines {
-            network.insert(&line[0..3], (&line[7..10], &line[12..15]));
-        }
+        let network = build_network(input_lines);
 
         let mut steps = 0;
-        let mut current = "AAA";
-        let target = "ZZZ";
+        let mut current = network.index("AAA").unwrap();
+        let target = network.index("ZZZ").unwrap();
 
         loop {
             for c in &directions {
-                current = match c {
-                    'L' => network[current].0,
-                    'R' => network[current].1,
-                    _ => panic!("Unknown direction"),
-                };
+                current = next_node(&network, current, *c);
                 steps += 1;
                 if current == target {
                     return steps.to_string();
@@ -39,47 +34,139 @@ impl Solver for Solver8 {
     }
 
     fn part2(&self, mut input_lines: Lines) -> String {
-        let mut steps: i64 = 0;
         let directions = input_lines.next().unwrap().chars().collect::<Vec<char>>();
         let _ = input_lines.next();
 
-        let mut current_nodes: Vec<&str> = Vec::new();
-        let mut network: HashMap<&str, (&str, &str)> = HashMap::new();
-        for line in input_lines {
-            if line.chars().nth(2).unwrap() == 'A' {
-                current_nodes.push(&line[0..3]);
+        let network = build_network(input_lines);
+        let paths: Vec<GhostPath> = (0..network.len())
+            .filter(|n| network.name(*n).ends_with('A'))
+            .map(|start| GhostPath::trace(&network, &directions, start))
+            .collect();
+
+        first_step_all_at_z(&paths).map_or("unsolvable".to_string(), |s| s.to_string())
+    }
+}
+
+// A ghost's state is its node plus its position in the directions, so after a tail of steps it
+// falls into a cycle of states
+#[derive(Debug, PartialEq)]
+struct GhostPath {
+    tail: usize,
+    cycle_length: usize,
+    // Up to the end of the first cycle
+    z_steps: Vec<usize>,
+}
+
+impl GhostPath {
+    fn trace(network: &Graph, directions: &[char], start: usize) -> GhostPath {
+        let mut first_seen: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut z_steps = Vec::new();
+        let mut node = start;
+
+        for step in 0.. {
+            let direction_ix = step % directions.len();
+            if let Some(&tail) = first_seen.get(&(node, direction_ix)) {
+                return GhostPath {
+                    tail,
+                    cycle_length: step - tail,
+                    z_steps,
+                };
+            }
+            first_seen.insert((node, direction_ix), step);
+
+            if network.name(node).ends_with('Z') {
+                z_steps.push(step);
             }
-            network.insert(&line[0..3], (&line[7..10], &line[12..15]));
+            node = next_node(network, node, directions[direction_ix]);
         }
+        unreachable!()
+    }
 
-        let num_locations = current_nodes.len();
-        let mut found_locs: HashMap<usize, i64> = HashMap::new();
-        loop {
-            for c in &directions {
-                steps += 1;
+    fn is_at_z(&self, step: usize) -> bool {
+        let step = if step < self.tail {
+            step
+        } else {
+            self.tail + (step - self.tail) % self.cycle_length
+        };
+        self.z_steps.contains(&step)
+    }
+}
 
-                for (ix, n) in current_nodes.iter_mut().enumerate() {
-                    *n = match c {
-                        'L' => network[n].0,
-                        'R' => network[n].1,
-                        _ => panic!("Unknown direction"),
-                    };
-                    if n.chars().nth(2).unwrap() == 'Z' {
-                        if !found_locs.contains_key(&ix) {
-                            found_locs.insert(ix, steps);
-                            if found_locs.len() == num_locations {
-                                let mut vals = found_locs.values();
-                                let mut lcm = vals.next().unwrap().clone();
-                                for s in vals {
-                                    lcm = integer::lcm(lcm, s.clone());
-                                }
-                                return lcm.to_string();
-                            }
-                        }
-                    }
-                }
+fn first_step_all_at_z(paths: &[GhostPath]) -> Option<i128> {
+    let longest_tail_path = paths.iter().max_by_key(|p| p.tail)?;
+    let longest_tail = longest_tail_path.tail;
+
+    // Until every ghost is in its cycle, check each of the (finite) Z steps of the ghost which
+    // takes longest to get there
+    if let Some(step) = longest_tail_path
+        .z_steps
+        .iter()
+        .filter(|s| **s < longest_tail)
+        .find(|s| paths.iter().all(|p| p.is_at_z(**s)))
+    {
+        return Some(*step as i128);
+    }
+
+    // After that, each Z in a ghost's cycle means the ghost is on a Z at any step congruent to it,
+    // modulo the cycle length.  Combine every choice of Z for each ghost with the CRT.
+    let mut congruences: Vec<(i128, i128)> = vec![(0, 1)];
+    for path in paths {
+        let cycle_length = path.cycle_length as i128;
+        let cycle_z_steps = path.z_steps.iter().filter(|s| **s >= path.tail);
+
+        congruences = congruences
+            .iter()
+            .cartesian_product(cycle_z_steps)
+            .filter_map(|(congruence, z_step)| {
+                combine_congruences(*congruence, (*z_step as i128, cycle_length))
+            })
+            .unique()
+            .collect();
+    }
+
+    // The earliest step satisfying each congruence, which isn't before all the ghosts are in their
+    // cycles
+    let longest_tail = longest_tail as i128;
+    congruences
+        .iter()
+        .map(|(remainder, modulus)| {
+            if *remainder >= longest_tail {
+                *remainder
+            } else {
+                remainder + Integer::div_ceil(&(longest_tail - remainder), modulus) * modulus
             }
-        }
+        })
+        .min()
+}
+
+// x = a (mod m) and x = b (mod n) as x = c (mod lcm(m, n)).  The moduli needn't be coprime.
+fn combine_congruences((a, m): (i128, i128), (b, n): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m.extended_gcd(&n);
+    if (b - a) % gcd != 0 {
+        return None;
+    }
+
+    // x is the inverse of m/gcd modulo n/gcd, so this makes a + m * k = b (mod n)
+    let lcm = m / gcd * n;
+    let k = ((b - a) / gcd * x).rem_euclid(n / gcd);
+    Some(((a + m * k).rem_euclid(lcm), lcm))
+}
+
+// Each node has two edges: left then right
+fn build_network(input_lines: Lines) -> Graph {
+    let mut network = Graph::new();
+    for line in input_lines {
+        network.add_edge(&line[0..3], &line[7..10]);
+        network.add_edge(&line[0..3], &line[12..15]);
+    }
+    network
+}
+
+fn next_node(network: &Graph, node: usize, direction: char) -> usize {
+    match direction {
+        'L' => network.neighbours(node)[0],
+        'R' => network.neighbours(node)[1],
+        _ => panic!("Unknown direction"),
     }
 }
 
@@ -113,4 +200,97 @@ ZZZ = (ZZZ, ZZZ)";
 XXX = (XXX, XXX)";
         assert_eq!(super::Solver8.part2(sample_input.lines()), "6");
     }
+
+    #[test]
+    fn ghosts_with_tails() {
+        // The first ghost is on a Z at odd steps from 3, and the second at steps 1, 4, 7, ...  so
+        // the first time they coincide isn't the LCM of their first Z steps.
+        let sample_input = "L
+
+11A = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+11Z = (11C, 11C)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)";
+        assert_eq!(super::Solver8.part2(sample_input.lines()), "7");
+        assert_eq!(brute_force(sample_input), Some(7));
+    }
+
+    #[test]
+    fn z_only_in_tail() {
+        let sample_input = "LR
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)";
+        assert_eq!(super::Solver8.part2(sample_input.lines()), "1");
+        assert_eq!(brute_force(sample_input), Some(1));
+    }
+
+    #[test]
+    fn multiple_zs_per_cycle() {
+        // The first ghost's cycle passes through two Z nodes
+        let sample_input = "LR
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (12Z, 12Z)
+12Z = (11D, 11D)
+11D = (11B, 11B)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)";
+        assert_eq!(super::Solver8.part2(sample_input.lines()), "9");
+        assert_eq!(brute_force(sample_input), Some(9));
+    }
+
+    #[test]
+    fn unsolvable() {
+        // One ghost is only ever on a Z at odd steps, and the other only at even ones
+        let sample_input = "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22B, 22B)";
+        assert_eq!(super::Solver8.part2(sample_input.lines()), "unsolvable");
+    }
+
+    #[test]
+    fn combine_congruences() {
+        use super::combine_congruences;
+        assert_eq!(combine_congruences((2, 3), (3, 5)), Some((8, 15)));
+        assert_eq!(combine_congruences((1, 4), (3, 6)), Some((9, 12)));
+        assert_eq!(combine_congruences((0, 4), (1, 6)), None);
+    }
+
+    fn brute_force(input: &str) -> Option<usize> {
+        let mut input_lines = input.lines();
+        let directions = input_lines.next().unwrap().chars().collect::<Vec<char>>();
+        let _ = input_lines.next();
+        let network = super::build_network(input_lines);
+
+        let mut nodes: Vec<usize> = (0..network.len())
+            .filter(|n| network.name(*n).ends_with('A'))
+            .collect();
+        for step in 1..10_000 {
+            for node in nodes.iter_mut() {
+                *node =
+                    super::next_node(&network, *node, directions[(step - 1) % directions.len()]);
+            }
+            if nodes.iter().all(|n| network.name(*n).ends_with('Z')) {
+                return Some(step);
+            }
+        }
+        None
+    }
 }
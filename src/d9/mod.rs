@@ -1,6 +1,10 @@
-use crate::Solver;
+use num::{BigInt, ToPrimitive, Zero};
 use std::str::Lines;
 
+use crate::params::Params;
+use crate::parse::signed_numbers;
+use crate::Solver;
+
 pub struct Solver9;
 
 impl Solver for Solver9 {
@@ -9,57 +13,88 @@ impl Solver for Solver9 {
     }
 
     fn part1(&self, input_lines: Lines) -> String {
-        let number_matcher = regex::Regex::new(r"-?\d+").unwrap();
-        let mut sum = 0;
-        for line in input_lines {
-            let original_numbers: Vec<i64> = number_matcher
-                .find_iter(line)
-                .map(|m| m.as_str().parse::<i64>().unwrap())
-                .collect();
-            sum += next_value(original_numbers);
-        }
-
-        sum.to_string()
+        self.part1_with_params(input_lines, &Params::new())
     }
 
     fn part2(&self, input_lines: Lines) -> String {
-        let number_matcher = regex::Regex::new(r"-?\d+").unwrap();
-        let mut sum = 0;
-        for line in input_lines {
-            let mut original_numbers: Vec<i64> = number_matcher
-                .find_iter(line)
-                .map(|m| m.as_str().parse::<i64>().unwrap())
-                .collect();
-            original_numbers.reverse();
-            sum += next_value(original_numbers);
-        }
+        self.part2_with_params(input_lines, &Params::new())
+    }
+
+    fn part1_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let steps = params.get_or("steps", 1);
+        input_lines
+            .map(|line| Sequence::fit(&signed_numbers::<i64>(line).collect::<Vec<_>>()))
+            .map(|sequence| sequence.forward(steps))
+            .sum::<BigInt>()
+            .to_string()
+    }
 
-        sum.to_string()
+    fn part2_with_params(&self, input_lines: Lines, params: &Params) -> String {
+        let steps = params.get_or("steps", 1);
+        input_lines
+            .map(|line| Sequence::fit(&signed_numbers::<i64>(line).collect::<Vec<_>>()))
+            .map(|sequence| sequence.backward(steps))
+            .sum::<BigInt>()
+            .to_string()
     }
 }
 
-fn next_value(numbers: Vec<i64>) -> i64 {
-    let (mut left, remaining) = numbers.split_first().unwrap();
-    let mut last: i64 = *left;
-    let mut differences: Vec<i64> = Vec::new();
-    let mut any_non_zero = false;
-
-    for n in remaining {
-        last = *n;
-        let diff = n - left;
-        left = n;
-        differences.push(diff);
-        if !any_non_zero && diff != 0 {
-            any_non_zero = true;
+// The polynomial through the values at 0, 1, 2, ..., stored as the first entry of each row of its
+// difference table
+#[derive(Debug, Clone, PartialEq)]
+struct Sequence {
+    leading_differences: Vec<BigInt>,
+    len: usize,
+}
+
+impl Sequence {
+    fn fit(values: &[i64]) -> Sequence {
+        let mut row: Vec<BigInt> = values.iter().map(|v| BigInt::from(*v)).collect();
+        let mut leading_differences = Vec::new();
+
+        while !row.is_empty() && row.iter().any(|d| !d.is_zero()) {
+            leading_differences.push(row[0].clone());
+            row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
         }
+
+        Sequence {
+            leading_differences,
+            len: values.len(),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn degree(&self) -> Option<usize> {
+        self.leading_differences.len().checked_sub(1)
+    }
+
+    // Newton's forward difference formula: the sum over k of the kth difference times x choose k
+    fn value_at(&self, x: i64) -> BigInt {
+        let x = BigInt::from(x);
+        let mut binomial = BigInt::from(1);
+        let mut value = BigInt::zero();
+
+        for (k, difference) in self.leading_differences.iter().enumerate() {
+            value += difference * &binomial;
+            // (x choose k) * (x - k) is always divisible by k + 1
+            binomial = binomial * (&x - k) / (k + 1);
+        }
+
+        value
+    }
+
+    #[allow(dead_code)]
+    fn checked_value_at(&self, x: i64) -> Option<i64> {
+        self.value_at(x).to_i64()
     }
 
-    if !any_non_zero {
-        return last;
+    fn forward(&self, steps: i64) -> BigInt {
+        self.value_at(self.len as i64 - 1 + steps)
     }
 
-    let next = next_value(differences);
-    next + last
+    fn backward(&self, steps: i64) -> BigInt {
+        self.value_at(-steps)
+    }
 }
 
 #[cfg(test)]
@@ -79,4 +114,71 @@ mod tests {
 10 13 16 21 30 45";
         assert_eq!(super::Solver9.part2(sample_input.lines()), "2");
     }
+
+    const SAMPLE_INPUT: &str = "0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45";
+
+    #[test]
+    fn steps_param() {
+        use crate::params::Params;
+
+        let mut params = Params::new();
+        params.insert("steps=3");
+        assert_eq!(
+            super::Solver9.part1_with_params(SAMPLE_INPUT.lines(), &params),
+            (24 + 45 + 146).to_string()
+        );
+        assert_eq!(
+            super::Solver9.part2_with_params(SAMPLE_INPUT.lines(), &params),
+            (-9 + 1 - 19).to_string()
+        );
+    }
+
+    #[test]
+    fn degrees() {
+        use super::Sequence;
+
+        assert_eq!(Sequence::fit(&[0, 3, 6, 9, 12, 15]).degree(), Some(1));
+        assert_eq!(Sequence::fit(&[1, 3, 6, 10, 15, 21]).degree(), Some(2));
+        assert_eq!(Sequence::fit(&[10, 13, 16, 21, 30, 45]).degree(), Some(3));
+        assert_eq!(Sequence::fit(&[7, 7, 7]).degree(), Some(0));
+        assert_eq!(Sequence::fit(&[0, 0]).degree(), None);
+        assert_eq!(Sequence::fit(&[]).degree(), None);
+    }
+
+    #[test]
+    fn matches_polynomial() {
+        use super::Sequence;
+        use num::BigInt;
+
+        let f = |x: i64| 2 * x * x * x - 5 * x * x + x - 7;
+        let values: Vec<i64> = (0..6).map(f).collect();
+        let sequence = Sequence::fit(&values);
+
+        assert_eq!(sequence.degree(), Some(3));
+        for x in -20..20 {
+            assert_eq!(sequence.value_at(x), BigInt::from(f(x)));
+        }
+        assert_eq!(sequence.forward(4), BigInt::from(f(9)));
+        assert_eq!(sequence.backward(4), BigInt::from(f(-4)));
+    }
+
+    #[test]
+    fn overflow() {
+        use super::Sequence;
+        use num::BigInt;
+
+        // x^3 at 10^7 is 10^21, which is too big for an i64
+        let sequence = Sequence::fit(&[0, 1, 8, 27, 64]);
+        assert_eq!(
+            sequence.checked_value_at(1_000_000),
+            Some(1_000_000_000_000_000_000)
+        );
+        assert_eq!(sequence.checked_value_at(10_000_000), None);
+        assert_eq!(
+            sequence.value_at(10_000_000),
+            "1000000000000000000000".parse::<BigInt>().unwrap()
+        );
+    }
 }
@@ -0,0 +1,222 @@
+// Lattice polygon helpers, shared by the days which need an enclosed area (d10, d18).
+//
+// All the polygons here have their vertices on integer grid points, so we can work entirely in
+// integers:
+// - the shoelace formula gives twice the (signed) area
+// - the number of lattice points on an edge is the gcd of its x and y extents
+// - Pick's theorem ties the two together: A = I + B/2 - 1
+//
+// The vertices are i64, but areas and counts are worked out in i128 so that big polygons can't
+// overflow.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+impl Polygon {
+    // The first vertex may be repeated at the end, or not
+    pub fn new(mut vertices: Vec<Point>) -> Polygon {
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        Polygon { vertices }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
+    }
+
+    // Kept doubled so that it's always an integer
+    pub fn double_area(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| a.x as i128 * b.y as i128 - b.x as i128 * a.y as i128)
+            .sum::<i128>()
+            .abs()
+    }
+
+    pub fn boundary_points(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| {
+                num::integer::gcd(
+                    (b.x as i128 - a.x as i128).abs(),
+                    (b.y as i128 - a.y as i128).abs(),
+                )
+            })
+            .sum()
+    }
+
+    pub fn interior_points(&self) -> i128 {
+        (self.double_area() - self.boundary_points() + 2) / 2
+    }
+
+    // Also the area covered when the edges are one square thick
+    pub fn area_including_boundary(&self) -> i128 {
+        self.interior_points() + self.boundary_points()
+    }
+
+    // Edges which cross or touch other than where consecutive edges meet, by the indices of their
+    // first vertices
+    pub fn self_intersection(&self) -> Option<(usize, usize)> {
+        let edges: Vec<(Point, Point)> = self.edges().collect();
+        let n = edges.len();
+
+        for i in 0..n {
+            for j in i + 1..n {
+                let (a, b) = edges[i];
+                let (c, d) = edges[j];
+                let intersects = if j == i + 1 {
+                    doubles_back(a, b, d)
+                } else if i == 0 && j == n - 1 {
+                    doubles_back(c, d, b)
+                } else {
+                    segments_intersect((a, b), (c, d))
+                };
+
+                if intersects {
+                    return Some((i, j));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Positive if a -> b -> c turns anticlockwise (with y up)
+fn cross(a: Point, b: Point, c: Point) -> i128 {
+    (b.x as i128 - a.x as i128) * (c.y as i128 - a.y as i128)
+        - (b.y as i128 - a.y as i128) * (c.x as i128 - a.x as i128)
+}
+
+// So on the segment if it's on the line
+fn within_box(p: Point, (a, b): (Point, Point)) -> bool {
+    a.x.min(b.x) <= p.x && p.x <= a.x.max(b.x) && a.y.min(b.y) <= p.y && p.y <= a.y.max(b.y)
+}
+
+fn doubles_back(a: Point, b: Point, c: Point) -> bool {
+    let dot = (b.x as i128 - a.x as i128) * (c.x as i128 - b.x as i128)
+        + (b.y as i128 - a.y as i128) * (c.y as i128 - b.y as i128);
+    cross(a, b, c) == 0 && dot < 0
+}
+
+fn segments_intersect((a, b): (Point, Point), (c, d): (Point, Point)) -> bool {
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+
+    if d1.signum() * d2.signum() < 0 && d3.signum() * d4.signum() < 0 {
+        return true;
+    }
+
+    // Otherwise they only meet if an end of one lies on the other
+    (d1 == 0 && within_box(a, (c, d)))
+        || (d2 == 0 && within_box(b, (c, d)))
+        || (d3 == 0 && within_box(c, (a, b)))
+        || (d4 == 0 && within_box(d, (a, b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Point, Polygon};
+
+    fn polygon(points: &[(i64, i64)]) -> Polygon {
+        Polygon::new(points.iter().map(|&(x, y)| Point { x, y }).collect())
+    }
+
+    #[test]
+    fn square() {
+        let square = polygon(&[(0, 0), (4, 0), (4, 4), (0, 4)]);
+        assert_eq!(square.double_area(), 32);
+        assert_eq!(square.boundary_points(), 16);
+        assert_eq!(square.interior_points(), 9);
+        assert_eq!(square.area_including_boundary(), 25);
+    }
+
+    #[test]
+    fn orientation_and_closing_vertex_dont_matter() {
+        let clockwise = polygon(&[(0, 0), (0, 3), (5, 3), (5, 0), (0, 0)]);
+        let anticlockwise = polygon(&[(0, 0), (5, 0), (5, 3), (0, 3)]);
+        assert_eq!(clockwise.double_area(), anticlockwise.double_area());
+        assert_eq!(clockwise.boundary_points(), anticlockwise.boundary_points());
+        assert_eq!(clockwise.interior_points(), 8);
+    }
+
+    #[test]
+    fn diagonal_edges() {
+        // Right-angled triangle with legs of 4: the hypotenuse passes through 3 interior lattice
+        // points, as well as its two end vertices.
+        let triangle = polygon(&[(0, 0), (4, 0), (0, 4)]);
+        assert_eq!(triangle.double_area(), 16);
+        assert_eq!(triangle.boundary_points(), 12);
+        assert_eq!(triangle.interior_points(), 3);
+    }
+
+    #[test]
+    fn concave() {
+        // A U shape, open at the top
+        let u_shape = polygon(&[
+            (0, 0),
+            (0, 4),
+            (6, 4),
+            (6, 0),
+            (4, 0),
+            (4, 2),
+            (2, 2),
+            (2, 0),
+        ]);
+        assert_eq!(u_shape.double_area(), 40);
+        assert_eq!(u_shape.boundary_points(), 24);
+        assert_eq!(u_shape.interior_points(), 9);
+        assert_eq!(u_shape.area_including_boundary(), 33);
+        assert_eq!(u_shape.self_intersection(), None);
+    }
+
+    #[test]
+    fn self_intersections() {
+        // A figure of eight, whose second and fourth edges cross
+        let bow_tie = polygon(&[(0, 0), (4, 0), (0, 4), (4, 4)]);
+        assert_eq!(bow_tie.self_intersection(), Some((1, 3)));
+
+        // Two squares touching at a corner
+        let touching = polygon(&[
+            (0, 0),
+            (2, 0),
+            (2, 2),
+            (4, 2),
+            (4, 4),
+            (2, 4),
+            (2, 2),
+            (0, 2),
+        ]);
+        assert_eq!(touching.self_intersection(), Some((1, 5)));
+
+        // Doubling back along an edge, including between the last and first edges
+        let spike = polygon(&[(0, 0), (4, 0), (4, 4), (4, 2), (0, 2)]);
+        assert_eq!(spike.self_intersection(), Some((1, 2)));
+        let spike = polygon(&[(2, 0), (4, 0), (4, 4), (0, 4), (0, 0), (3, 0)]);
+        assert!(spike.self_intersection().is_some());
+
+        let triangle = polygon(&[(0, 0), (4, 0), (0, 4)]);
+        assert_eq!(triangle.self_intersection(), None);
+    }
+
+    #[test]
+    fn big_coordinates() {
+        // The shoelace products would overflow an i64
+        let big = 4_000_000_000_000;
+        let square = polygon(&[(0, 0), (big, 0), (big, big), (0, big)]);
+        assert_eq!(square.double_area(), 2 * big as i128 * big as i128);
+        assert_eq!(
+            square.area_including_boundary(),
+            (big as i128 + 1) * (big as i128 + 1)
+        );
+    }
+}
@@ -0,0 +1,497 @@
+// Directed graph keyed by node name, for the days whose input is a network of named nodes.
+//
+// Names are mapped to dense indices as they're first seen, and all the algorithms work on the
+// indices.  Each node's outgoing edges are kept in the order they were added, so the position of
+// an edge can carry meaning (e.g. left/right in d8).  Undirected graphs are stored with an edge in
+// each direction.
+
+use std::{
+    cmp::min,
+    collections::{BinaryHeap, HashMap, VecDeque},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Graph<'a> {
+    names: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new() -> Graph<'a> {
+        Graph::default()
+    }
+
+    // Nodes 0..len with no names, for callers which already number their nodes
+    pub fn with_len(len: usize) -> Graph<'a> {
+        Graph {
+            names: vec![""; len],
+            indices: HashMap::new(),
+            edges: vec![Vec::new(); len],
+        }
+    }
+
+    // Adds the node if it isn't there already
+    pub fn node(&mut self, name: &'a str) -> usize {
+        if let Some(ix) = self.indices.get(name) {
+            return *ix;
+        }
+
+        let ix = self.names.len();
+        self.names.push(name);
+        self.indices.insert(name, ix);
+        self.edges.push(Vec::new());
+        ix
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    pub fn name(&self, ix: usize) -> &'a str {
+        self.names[ix]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn add_edge(&mut self, from: &'a str, to: &'a str) {
+        let from = self.node(from);
+        let to = self.node(to);
+        self.add_edge_ix(from, to);
+    }
+
+    pub fn add_edge_ix(&mut self, from: usize, to: usize) {
+        self.edges[from].push(to);
+    }
+
+    // The methods marked dead_code are ready for the later graph puzzles
+    #[allow(dead_code)]
+    pub fn add_undirected_edge(&mut self, a: &'a str, b: &'a str) {
+        self.add_edge(a, b);
+        self.add_edge(b, a);
+    }
+
+    pub fn neighbours(&self, ix: usize) -> &[usize] {
+        &self.edges[ix]
+    }
+
+    #[allow(dead_code)]
+    pub fn bfs(&self, start: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.len()];
+        let mut queue = VecDeque::from([start]);
+        distances[start] = Some(0);
+
+        while let Some(node) = queue.pop_front() {
+            let next_distance = distances[node].map(|d| d + 1);
+            for &next in &self.edges[node] {
+                if distances[next].is_none() {
+                    distances[next] = next_distance;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Pre-order
+    #[allow(dead_code)]
+    pub fn dfs(&self, start: usize) -> Vec<usize> {
+        let mut visited = vec![false; self.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![start];
+
+        while let Some(node) = stack.pop() {
+            if visited[node] {
+                continue;
+            }
+            visited[node] = true;
+            order.push(node);
+
+            // Push in reverse so that the first edge is followed first
+            stack.extend(self.edges[node].iter().rev().filter(|n| !visited[**n]));
+        }
+
+        order
+    }
+
+    #[allow(dead_code)]
+    pub fn topological_sort(&self) -> Option<Vec<usize>> {
+        let mut in_degrees = vec![0; self.len()];
+        for &to in self.edges.iter().flatten() {
+            in_degrees[to] += 1;
+        }
+
+        let mut ready: VecDeque<usize> = (0..self.len()).filter(|n| in_degrees[*n] == 0).collect();
+        let mut order = Vec::with_capacity(self.len());
+
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for &next in &self.edges[node] {
+                in_degrees[next] -= 1;
+                if in_degrees[next] == 0 {
+                    ready.push_back(next);
+                }
+            }
+        }
+
+        (order.len() == self.len()).then_some(order)
+    }
+
+    #[allow(dead_code)]
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        // Union-find, so that edge direction doesn't matter
+        let mut parents: Vec<usize> = (0..self.len()).collect();
+
+        fn root(parents: &mut [usize], mut node: usize) -> usize {
+            while parents[node] != node {
+                parents[node] = parents[parents[node]];
+                node = parents[node];
+            }
+            node
+        }
+
+        for (from, tos) in self.edges.iter().enumerate() {
+            for &to in tos {
+                let from_root = root(&mut parents, from);
+                let to_root = root(&mut parents, to);
+                parents[from_root] = to_root;
+            }
+        }
+
+        let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+        for node in 0..self.len() {
+            let node_root = root(&mut parents, node);
+            components.entry(node_root).or_default().push(node);
+        }
+
+        let mut components: Vec<Vec<usize>> = components.into_values().collect();
+        components.sort();
+        components
+    }
+
+    // Tarjan's algorithm, so components come out in reverse topological order
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        const UNVISITED: usize = usize::MAX;
+
+        let mut indices = vec![UNVISITED; self.len()];
+        let mut low_links = vec![0; self.len()];
+        let mut on_stack = vec![false; self.len()];
+        let mut stack = Vec::new();
+        let mut next_index = 0;
+        let mut components = Vec::new();
+
+        for root in 0..self.len() {
+            if indices[root] != UNVISITED {
+                continue;
+            }
+
+            // Explicit call stack of (node, next edge to follow), rather than recursing
+            let mut call_stack = vec![(root, 0)];
+            indices[root] = next_index;
+            low_links[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&(node, edge_ix)) = call_stack.last() {
+                if let Some(&next) = self.edges[node].get(edge_ix) {
+                    call_stack.last_mut().unwrap().1 += 1;
+
+                    if indices[next] == UNVISITED {
+                        indices[next] = next_index;
+                        low_links[next] = next_index;
+                        next_index += 1;
+                        stack.push(next);
+                        on_stack[next] = true;
+                        call_stack.push((next, 0));
+                    } else if on_stack[next] {
+                        low_links[node] = min(low_links[node], indices[next]);
+                    }
+                    continue;
+                }
+
+                // Finished with this node
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    low_links[parent] = min(low_links[parent], low_links[node]);
+                }
+
+                if low_links[node] == indices[node] {
+                    let mut component = Vec::new();
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+
+        components
+    }
+
+    // Stoer-Wagner, for undirected graphs.  Gives the number of edges cut and one side of the cut.
+    #[allow(dead_code)]
+    pub fn min_cut(&self) -> (u64, Vec<usize>) {
+        if self.len() < 2 {
+            return (0, (0..self.len()).collect());
+        }
+
+        // Edge weights between the merged groups of nodes.  Only groups which are still active have
+        // entries.
+        let mut weights: Vec<HashMap<usize, u64>> = vec![HashMap::new(); self.len()];
+        for (from, tos) in self.edges.iter().enumerate() {
+            for &to in tos {
+                if from != to {
+                    *weights[from].entry(to).or_default() += 1;
+                }
+            }
+        }
+        let mut members: Vec<Vec<usize>> = (0..self.len()).map(|n| vec![n]).collect();
+        let mut active: Vec<usize> = (0..self.len()).collect();
+
+        let mut best_cut = (u64::MAX, Vec::new());
+
+        while active.len() > 1 {
+            // Add groups one at a time, always picking the one most tightly connected to those
+            // already added.  The last two added are then merged.
+            let mut added = vec![false; self.len()];
+            let mut connection = vec![0; self.len()];
+            let mut queue = BinaryHeap::from([(0, active[0])]);
+            let mut order = Vec::with_capacity(active.len());
+
+            while let Some((weight, group)) = queue.pop() {
+                if added[group] || weight != connection[group] {
+                    continue;
+                }
+                added[group] = true;
+                order.push(group);
+
+                for (&next, &edge_weight) in &weights[group] {
+                    if !added[next] {
+                        connection[next] += edge_weight;
+                        queue.push((connection[next], next));
+                    }
+                }
+            }
+
+            if order.len() < active.len() {
+                // Not everything could be reached, so the graph is already in pieces
+                let side = order.iter().flat_map(|g| members[*g].clone()).collect();
+                return (0, side);
+            }
+
+            let last = order[order.len() - 1];
+            let second_last = order[order.len() - 2];
+
+            if connection[last] < best_cut.0 {
+                best_cut = (connection[last], members[last].clone());
+            }
+
+            // Merge the last group into the second last
+            let last_members = std::mem::take(&mut members[last]);
+            members[second_last].extend(last_members);
+            for (next, edge_weight) in std::mem::take(&mut weights[last]) {
+                weights[next].remove(&last);
+                if next != second_last {
+                    *weights[second_last].entry(next).or_default() += edge_weight;
+                    *weights[next].entry(second_last).or_default() += edge_weight;
+                }
+            }
+            active.retain(|g| *g != last);
+        }
+
+        best_cut.1.sort();
+        best_cut
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graph;
+
+    fn directed<'a>(edges: &[(&'a str, &'a str)]) -> Graph<'a> {
+        let mut graph = Graph::new();
+        for (from, to) in edges {
+            graph.add_edge(from, to);
+        }
+        graph
+    }
+
+    fn undirected<'a>(edges: &[(&'a str, &'a str)]) -> Graph<'a> {
+        let mut graph = Graph::new();
+        for (a, b) in edges {
+            graph.add_undirected_edge(a, b);
+        }
+        graph
+    }
+
+    fn names<'a>(graph: &Graph<'a>, nodes: &[usize]) -> Vec<&'a str> {
+        let mut names: Vec<&str> = nodes.iter().map(|n| graph.name(*n)).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn node_indices() {
+        let mut graph = directed(&[("a", "b"), ("b", "c"), ("a", "c")]);
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph.index("c"), Some(2));
+        assert_eq!(graph.index("d"), None);
+        assert_eq!(graph.node("b"), 1);
+        assert_eq!(graph.name(0), "a");
+        assert_eq!(graph.neighbours(0), &[1, 2]);
+    }
+
+    #[test]
+    fn searches() {
+        let graph = directed(&[
+            ("a", "b"),
+            ("a", "c"),
+            ("b", "d"),
+            ("c", "d"),
+            ("d", "e"),
+            ("f", "a"),
+        ]);
+        let a = graph.index("a").unwrap();
+
+        let distances = graph.bfs(a);
+        assert_eq!(distances[graph.index("d").unwrap()], Some(2));
+        assert_eq!(distances[graph.index("e").unwrap()], Some(3));
+        assert_eq!(distances[graph.index("f").unwrap()], None);
+
+        let order: Vec<&str> = graph.dfs(a).iter().map(|n| graph.name(*n)).collect();
+        assert_eq!(order, vec!["a", "b", "d", "e", "c"]);
+    }
+
+    #[test]
+    fn topological_sort() {
+        let graph = directed(&[
+            ("shirt", "tie"),
+            ("tie", "jacket"),
+            ("trousers", "shoes"),
+            ("trousers", "belt"),
+            ("belt", "jacket"),
+            ("shirt", "belt"),
+            ("socks", "shoes"),
+        ]);
+        let order = graph.topological_sort().unwrap();
+        assert_eq!(order.len(), graph.len());
+        for from in 0..graph.len() {
+            for to in graph.neighbours(from) {
+                let from_pos = order.iter().position(|n| *n == from);
+                let to_pos = order.iter().position(|n| n == to);
+                assert!(from_pos < to_pos);
+            }
+        }
+
+        let cyclic = directed(&[("a", "b"), ("b", "c"), ("c", "a")]);
+        assert_eq!(cyclic.topological_sort(), None);
+    }
+
+    #[test]
+    fn components() {
+        let graph = directed(&[("a", "b"), ("c", "b"), ("d", "e"), ("f", "f")]);
+        let components: Vec<Vec<&str>> = graph
+            .connected_components()
+            .iter()
+            .map(|c| names(&graph, c))
+            .collect();
+        assert_eq!(
+            components,
+            vec![vec!["a", "b", "c"], vec!["d", "e"], vec!["f"]]
+        );
+    }
+
+    #[test]
+    fn strongly_connected_components() {
+        let graph = directed(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("c", "d"),
+            ("d", "e"),
+            ("e", "d"),
+            ("e", "f"),
+        ]);
+        let components: Vec<Vec<&str>> = graph
+            .strongly_connected_components()
+            .iter()
+            .map(|c| names(&graph, c))
+            .collect();
+
+        // Reverse topological order
+        assert_eq!(
+            components,
+            vec![vec!["f"], vec!["d", "e"], vec!["a", "b", "c"]]
+        );
+    }
+
+    #[test]
+    fn numbered_nodes() {
+        let mut graph = Graph::with_len(4);
+        graph.add_edge_ix(0, 1);
+        graph.add_edge_ix(1, 0);
+        graph.add_edge_ix(1, 2);
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.neighbours(1), &[0, 2]);
+        assert_eq!(
+            graph.strongly_connected_components(),
+            vec![vec![2], vec![1, 0], vec![3]]
+        );
+    }
+
+    #[test]
+    fn min_cut() {
+        // Two triangles joined by a single edge, with one triangle edge doubled up
+        let graph = undirected(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("a", "b"),
+            ("c", "d"),
+            ("d", "e"),
+            ("e", "f"),
+            ("f", "d"),
+        ]);
+        let (cut, side) = graph.min_cut();
+        assert_eq!(cut, 1);
+        let side = names(&graph, &side);
+        assert!(side == vec!["a", "b", "c"] || side == vec!["d", "e", "f"]);
+
+        // The example from 2023 day 25
+        let mut graph = Graph::new();
+        let day25 = "jqt: rhn xhk nvd
+rsh: frs pzl lsr
+xhk: hfx
+cmg: qnr nvd lhk bvb
+rhn: xhk bvb hfx
+bvb: xhk hfx
+pzl: lsr hfx nvd
+qnr: nvd
+ntq: jqt hfx bvb xhk
+nvd: lhk
+lsr: lhk
+rzs: qnr cmg lsr rsh
+frs: qnr lhk lsr";
+        for line in day25.lines() {
+            let (from, tos) = line.split_once(": ").unwrap();
+            for to in tos.split(' ') {
+                graph.add_undirected_edge(from, to);
+            }
+        }
+        let (cut, side) = graph.min_cut();
+        assert_eq!(cut, 3);
+        assert_eq!(side.len() * (graph.len() - side.len()), 54);
+
+        let disconnected = undirected(&[("a", "b"), ("c", "d")]);
+        assert_eq!(disconnected.min_cut().0, 0);
+    }
+}
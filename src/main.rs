@@ -23,6 +23,11 @@ mod d6;
 mod d7;
 mod d8;
 mod d9;
+mod geometry;
+mod graph;
+mod memo;
+mod params;
+mod parse;
 
 use crate::d1::Solver1;
 use crate::d10::Solver10;
@@ -49,45 +54,96 @@ use crate::d6::Solver6;
 use crate::d7::Solver7;
 use crate::d8::Solver8;
 use crate::d9::Solver9;
+use crate::params::Params;
 use std::{env, str::Lines, vec};
 
 trait Solver {
     fn day_number(&self) -> u32;
 
-    fn solve(&self) -> String {
-        let input_text =
-            std::fs::read_to_string(format!("src/d{}/input.txt", self.day_number())).unwrap();
+    fn input(&self) -> String {
+        std::fs::read_to_string(format!("src/d{}/input.txt", self.day_number())).unwrap()
+    }
+
+    fn solve(&self, params: &Params) -> String {
+        let input_text = self.input();
         let lines = input_text.lines();
 
-        let part1_soln = self.part1(lines.clone());
-        let part2_soln = self.part2(lines);
+        let part1_soln = self.part1_with_params(lines.clone(), params);
+        let part2_soln = self.part2_with_params(lines, params);
 
         format!("part 1: {part1_soln:>15}\tpart 2: {part2_soln:>15}")
     }
 
     fn part1(&self, lines: Lines) -> String;
     fn part2(&self, lines: Lines) -> String;
+
+    // Solvers which can be tweaked with command line parameters override these.  Otherwise the
+    // parameters are ignored.
+    fn part1_with_params(&self, lines: Lines, _params: &Params) -> String {
+        self.part1(lines)
+    }
+
+    fn part2_with_params(&self, lines: Lines, _params: &Params) -> String {
+        self.part2(lines)
+    }
+
+    // Solvers which can say something about the structure of their input override this, for the
+    // `--analyze` mode.
+    fn analyze(&self, _lines: Lines, _params: &Params) -> String {
+        "no analysis for this day".to_string()
+    }
 }
 
 fn main() {
-    let args = env::args().skip(1); // Skip the executable name
+    let mut args = env::args().skip(1); // Skip the executable name
 
     let mut days = vec![];
     let mut threads = vec![];
     let mut use_threads: bool = false;
+    let mut analyze = false;
+    // Parameters before the first day apply to every day, and ones after a day just to that day
+    let mut params = Params::new();
+    let mut day_params: Vec<Params> = vec![];
 
-    for arg in args {
+    while let Some(arg) = args.next() {
         if arg == "t" {
             println!("Using threads");
             use_threads = true;
             continue;
         }
 
+        if arg == "--analyze" {
+            analyze = true;
+            continue;
+        }
+
+        if arg == "--param" {
+            let key_value = args.next().expect("--param needs a key=value argument");
+            match day_params.last_mut() {
+                Some(day_params) => day_params.insert(&key_value),
+                None => params.insert(&key_value),
+            }
+            continue;
+        }
+
         days.push(arg.parse::<u32>().unwrap());
+        day_params.push(Params::new());
     }
 
-    for day in days {
-        let day_soln = move || println!("{day:>2}: {}", get_solver_from_day(day).solve());
+    for (day, day_params) in days.into_iter().zip(day_params) {
+        let mut params = params.clone();
+        params.extend(&day_params);
+        let day_soln = move || {
+            let solver = get_solver_from_day(day);
+            if analyze {
+                println!(
+                    "{day:>2}:\n{}",
+                    solver.analyze(solver.input().lines(), &params)
+                );
+            } else {
+                println!("{day:>2}: {}", solver.solve(&params));
+            }
+        };
 
         if use_threads {
             threads.push(std::thread::spawn(day_soln));
@@ -97,7 +153,7 @@ fn main() {
     }
 
     for t in threads {
-        _ = t.join().unwrap();
+        t.join().unwrap();
     }
 }
 
@@ -0,0 +1,107 @@
+// Caches for memoising recursive counting functions (e.g. d12).
+//
+// A recursive function takes a `&mut impl Memo<K, V>` and wraps its body in `get_or_compute`.  The
+// body is handed the cache back so that it can recurse, and only runs if the key hasn't been seen
+// before.
+
+use std::{collections::HashMap, hash::Hash};
+
+pub trait Memo<K, V: Copy> {
+    fn get(&self, key: &K) -> Option<V>;
+    fn insert(&mut self, key: K, value: V);
+
+    fn get_or_compute(&mut self, key: K, compute: impl FnOnce(&mut Self) -> V) -> V
+    where
+        Self: Sized,
+    {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+
+        let value = compute(self);
+        self.insert(key, value);
+        value
+    }
+}
+
+pub struct HashMemo<K, V> {
+    values: HashMap<K, V>,
+}
+
+impl<K, V> HashMemo<K, V> {
+    pub fn new() -> HashMemo<K, V> {
+        HashMemo {
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: Copy> Memo<K, V> for HashMemo<K, V> {
+    fn get(&self, key: &K) -> Option<V> {
+        self.values.get(key).copied()
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.values.insert(key, value);
+    }
+}
+
+// Both parts of the key must be less than the dimensions given to `new`
+pub struct GridMemo<V> {
+    height: usize,
+    values: Vec<Option<V>>,
+}
+
+impl<V: Copy> GridMemo<V> {
+    pub fn new(width: usize, height: usize) -> GridMemo<V> {
+        GridMemo {
+            height,
+            values: vec![None; width * height],
+        }
+    }
+}
+
+impl<V: Copy> Memo<(usize, usize), V> for GridMemo<V> {
+    fn get(&self, key: &(usize, usize)) -> Option<V> {
+        self.values[key.0 * self.height + key.1]
+    }
+
+    fn insert(&mut self, key: (usize, usize), value: V) {
+        self.values[key.0 * self.height + key.1] = Some(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GridMemo, HashMemo, Memo};
+
+    fn fibonacci(memo: &mut impl Memo<u64, u64>, n: u64) -> u64 {
+        memo.get_or_compute(n, |memo| match n {
+            0 | 1 => n,
+            _ => fibonacci(memo, n - 1) + fibonacci(memo, n - 2),
+        })
+    }
+
+    fn lattice_paths(memo: &mut impl Memo<(usize, usize), u64>, x: usize, y: usize) -> u64 {
+        memo.get_or_compute((x, y), |memo| match (x, y) {
+            (0, _) | (_, 0) => 1,
+            _ => lattice_paths(memo, x - 1, y) + lattice_paths(memo, x, y - 1),
+        })
+    }
+
+    #[test]
+    fn hash_memo() {
+        // Far too slow to finish without the cache
+        assert_eq!(fibonacci(&mut HashMemo::new(), 90), 2880067194370816120);
+    }
+
+    #[test]
+    fn grid_memo() {
+        assert_eq!(lattice_paths(&mut GridMemo::new(17, 17), 16, 16), 601080390);
+        assert_eq!(lattice_paths(&mut GridMemo::new(4, 3), 3, 2), 10);
+        assert_eq!(
+            lattice_paths(&mut HashMemo::new(), 16, 16),
+            lattice_paths(&mut GridMemo::new(17, 17), 16, 16)
+        );
+    }
+}
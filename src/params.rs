@@ -0,0 +1,71 @@
+// Optional `key=value` parameters for the solvers, passed on the command line with `--param`.
+
+use std::{collections::HashMap, fmt::Debug, str::FromStr};
+
+#[derive(Debug, Clone, Default)]
+pub struct Params {
+    values: HashMap<String, String>,
+}
+
+impl Params {
+    pub fn new() -> Params {
+        Params::default()
+    }
+
+    pub fn insert(&mut self, key_value: &str) {
+        let (key, value) = key_value
+            .split_once('=')
+            .unwrap_or_else(|| panic!("Parameters must be key=value, not {key_value}"));
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    // Replaces any with the same keys
+    pub fn extend(&mut self, other: &Params) {
+        self.values
+            .extend(other.values.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    pub fn get_or<T>(&self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Debug,
+    {
+        match self.get(key) {
+            Some(value) => value
+                .parse::<T>()
+                .unwrap_or_else(|e| panic!("Invalid value {value} for {key}: {e:?}")),
+            None => default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Params;
+
+    #[test]
+    fn params() {
+        let mut params = Params::new();
+        params.insert("bag=red:1,blue:2");
+        params.insert("size=10");
+        params.insert("size=20");
+
+        assert_eq!(params.get("bag"), Some("red:1,blue:2"));
+        assert_eq!(params.get("size"), Some("20"));
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(params.get_or("size", 2), 20);
+        assert_eq!(params.get_or("missing", '*'), '*');
+
+        let mut day_params = Params::new();
+        day_params.insert("size=30");
+        day_params.insert("method=fast");
+        params.extend(&day_params);
+        assert_eq!(params.get("size"), Some("30"));
+        assert_eq!(params.get("method"), Some("fast"));
+        assert_eq!(params.get("bag"), Some("red:1,blue:2"));
+    }
+}
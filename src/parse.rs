@@ -0,0 +1,132 @@
+// Helpers for pulling values out of puzzle input lines without regexes.
+
+use std::{fmt::Debug, marker::PhantomData, str::FromStr};
+
+pub struct Numbers<'a, T> {
+    text: &'a str,
+    position: usize,
+    signed: bool,
+    number_type: PhantomData<T>,
+}
+
+impl<T> Iterator for Numbers<'_, T>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let bytes = self.text.as_bytes();
+
+        let start = (self.position..bytes.len()).find(|ix| bytes[*ix].is_ascii_digit())?;
+        let end = (start..bytes.len())
+            .find(|ix| !bytes[*ix].is_ascii_digit())
+            .unwrap_or(bytes.len());
+        self.position = end;
+
+        let start = if self.signed && start > 0 && bytes[start - 1] == b'-' {
+            start - 1
+        } else {
+            start
+        };
+
+        Some(self.text[start..end].parse::<T>().unwrap())
+    }
+}
+
+// Any '-' signs are ignored
+pub fn unsigned_numbers<T>(text: &str) -> Numbers<'_, T> {
+    Numbers {
+        text,
+        position: 0,
+        signed: false,
+        number_type: PhantomData,
+    }
+}
+
+// Numbers immediately preceded by '-' are negative
+pub fn signed_numbers<T>(text: &str) -> Numbers<'_, T> {
+    Numbers {
+        text,
+        position: 0,
+        signed: true,
+        number_type: PhantomData,
+    }
+}
+
+// `name{contents}`, where the name may be empty
+pub fn braced(text: &str) -> (&str, &str) {
+    let (name, contents) = text.split_once('{').unwrap();
+    (name, contents.strip_suffix('}').unwrap())
+}
+
+pub fn key_values(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split(',')
+        .map(|pair| pair.trim().split_once('=').unwrap())
+}
+
+// Consumes the blank line too
+pub fn next_section<'a, 'b>(
+    lines: &'b mut impl Iterator<Item = &'a str>,
+) -> impl Iterator<Item = &'a str> + 'b {
+    lines.take_while(|line| !line.is_empty())
+}
+
+// Runs of several blank lines don't produce empty sections
+pub fn sections<'a>(lines: impl Iterator<Item = &'a str>) -> impl Iterator<Item = Vec<&'a str>> {
+    let mut lines = lines.peekable();
+    std::iter::from_fn(move || {
+        while lines.next_if(|line| line.is_empty()).is_some() {}
+        lines.peek()?;
+        Some(next_section(&mut lines).collect())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{braced, key_values, next_section, sections, signed_numbers, unsigned_numbers};
+
+    #[test]
+    fn numbers() {
+        let text = "Card  1: 41 48 -83 |x7-2";
+        assert_eq!(
+            unsigned_numbers::<u32>(text).collect::<Vec<_>>(),
+            vec![1, 41, 48, 83, 7, 2]
+        );
+        assert_eq!(
+            signed_numbers::<i64>(text).collect::<Vec<_>>(),
+            vec![1, 41, 48, -83, 7, -2]
+        );
+        assert_eq!(unsigned_numbers::<u8>("no numbers - here").next(), None);
+        assert_eq!(signed_numbers::<i32>("-").next(), None);
+    }
+
+    #[test]
+    fn braces_and_pairs() {
+        assert_eq!(
+            braced("px{a<2006:qkq,m>2090:A,rhg}"),
+            ("px", "a<2006:qkq,m>2090:A,rhg")
+        );
+
+        let (name, contents) = braced("{x=787,m=2655}");
+        assert_eq!(name, "");
+        assert_eq!(
+            key_values(contents).collect::<Vec<_>>(),
+            vec![("x", "787"), ("m", "2655")]
+        );
+    }
+
+    #[test]
+    fn blank_line_sections() {
+        let text = "a\nb\n\nc\n\n\nd\n";
+        assert_eq!(
+            sections(text.lines()).collect::<Vec<_>>(),
+            vec![vec!["a", "b"], vec!["c"], vec!["d"]]
+        );
+
+        let mut lines = text.lines();
+        assert_eq!(next_section(&mut lines).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(lines.next(), Some("c"));
+    }
+}